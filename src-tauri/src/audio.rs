@@ -1,46 +1,609 @@
+use crate::audio_utils::{ChannelSelection, GainSettings};
+use crate::error::AppError;
+use crate::meter::{self, Meter};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, SupportedBufferSize};
 use rodio::OutputStreamBuilder;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, mpsc};
-use tauri::{Emitter, State, WebviewWindow};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use system::{Mixer, SystemDevice};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewWindow};
+
+mod system;
+
+/// cpal has no hot-plug notifications, so the device list is polled this often while
+/// it is on screen.
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the drain thread empties the capture ring buffer.
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+/// Ring buffer capacity in seconds of audio. It only fills up if the drain thread stalls.
+const RING_SECONDS: usize = 1;
+/// Longest pre-roll a warm capture keeps.
+const MAX_PREROLL_SECS: f32 = 10.0;
+/// How long before a recording limit the warning is sent.
+const LIMIT_WARNING_SECS: u32 = 30;
 
 pub struct AudioState {
     pub is_recording: Mutex<bool>,
     pub recording_buffer: Arc<Mutex<Vec<f32>>>,
-    pub stop_tx: Mutex<Option<mpsc::Sender<()>>>,
+    capture: Mutex<Option<Capture>>,
     pub format: Mutex<Option<(u32, u16)>>, // sample_rate, channels
+    /// Downmix used for the current recording.
+    pub channel_selection: Mutex<ChannelSelection>,
+    /// Gain stage used for the current recording.
+    pub gain: Mutex<GainSettings>,
+    /// Settings for keeping the microphone open between recordings, if enabled.
+    warm: Mutex<Option<WarmCapture>>,
+    /// Host the device list was last requested for, which the hot-plug watcher polls.
+    device_host: Mutex<Option<String>>,
+    /// Whether the device list is on screen; enumerating is too slow on ALSA to do it
+    /// all the time.
+    watch_devices: AtomicBool,
+    hosts: Hosts,
     pub model_path: Mutex<Option<String>>,
 }
 
+/// What the tray shows about the microphone.
+pub enum MicStatus {
+    Off,
+    /// Open while idle, keeping only the pre-roll.
+    PreRoll,
+    Recording,
+    /// Recording, with the stream open but samples discarded.
+    Paused,
+}
+
 impl AudioState {
     pub fn new() -> Self {
         Self {
             is_recording: Mutex::new(false),
             recording_buffer: Arc::new(Mutex::new(Vec::new())),
-            stop_tx: Mutex::new(None),
+            capture: Mutex::new(None),
             format: Mutex::new(None),
+            channel_selection: Mutex::new(ChannelSelection::default()),
+            gain: Mutex::new(GainSettings::default()),
+            warm: Mutex::new(None),
+            device_host: Mutex::new(None),
+            watch_devices: AtomicBool::new(false),
+            hosts: Hosts::default(),
             model_path: Mutex::new(None),
         }
     }
+
+    /// The host the frontend last listed devices for.
+    fn listed_host(&self) -> Result<Arc<cpal::Host>, AppError> {
+        self.hosts.get(self.device_host.lock()?.as_deref())
+    }
+
+    pub fn mic_status(&self) -> MicStatus {
+        match self.capture.lock().as_deref() {
+            Ok(Some(capture)) if capture.sink.paused.load(Ordering::Acquire) => MicStatus::Paused,
+            Ok(Some(capture)) if capture.sink.recording.load(Ordering::Acquire) => {
+                MicStatus::Recording
+            }
+            Ok(Some(_)) => MicStatus::PreRoll,
+            _ => MicStatus::Off,
+        }
+    }
+}
+
+/// The device and settings a capture was opened with, to tell whether a warm capture
+/// can be reused for a recording.
+#[derive(Clone, Debug, PartialEq)]
+struct CaptureRequest {
+    device_id: Option<String>,
+    config: CaptureConfig,
+}
+
+#[derive(Clone)]
+struct WarmCapture {
+    request: CaptureRequest,
+    preroll_secs: f32,
+}
+
+/// The thread that owns the input stream. cpal streams are not `Send` on every platform,
+/// so the stream is built, played and dropped on this thread.
+struct Capture {
+    sink: CaptureSink,
+    thread: JoinHandle<()>,
+    request: CaptureRequest,
+    device_name: String,
+    format: (u32, u16),
+    /// The requested device was missing and the default device is used instead.
+    fallback: bool,
+}
+
+/// Shared by every stream of one capture, including the ones opened after the device
+/// was unplugged.
+#[derive(Clone)]
+struct CaptureSink {
+    buffer: Arc<Mutex<Vec<f32>>>,
+    window: WebviewWindow,
+    control_tx: mpsc::Sender<CaptureEvent>,
+    /// Cleared while a warm capture only keeps the pre-roll.
+    recording: Arc<AtomicBool>,
+    /// Set while a recording is paused; samples are dropped instead of appended.
+    paused: Arc<AtomicBool>,
+    /// Seconds of audio kept while not recording.
+    preroll_secs: f32,
+    progress: Arc<Mutex<Progress>>,
+}
+
+/// How far the current recording is, shared so a stream reopened after an unplug
+/// continues where the last one stopped.
+#[derive(Default)]
+struct Progress {
+    /// The recording file, when the capture is spilled to disk.
+    spill: Option<hound::WavWriter<BufWriter<File>>>,
+    /// Writing to disk failed; the rest of the recording stays in memory.
+    spill_failed: bool,
+    warned: bool,
+    /// The limit was hit; samples are dropped until the recording stops.
+    full: bool,
+}
+
+impl Progress {
+    fn spilled(&self) -> usize {
+        self.spill
+            .as_ref()
+            .map_or(0, |writer| writer.len() as usize)
+    }
+}
+
+#[derive(Clone)]
+enum CaptureEvent {
+    Stop,
+    /// The microphone's stream reported that its device went away.
+    DeviceLost,
+    /// System audio stopped and won't come back; the monitor has already retried.
+    SystemLost(String),
+}
+
+/// Tells the capture thread that a stream's device went away.
+#[derive(Clone)]
+struct LossReport {
+    control_tx: mpsc::Sender<CaptureEvent>,
+    event: CaptureEvent,
+}
+
+impl LossReport {
+    fn send(&self) {
+        let _ = self.control_tx.send(self.event.clone());
+    }
+}
+
+impl Capture {
+    /// Stop the stream and wait until the thread has dropped it, so no callback can
+    /// write to the buffer afterwards.
+    fn stop(self) -> Result<(), AppError> {
+        let _ = self.sink.control_tx.send(CaptureEvent::Stop);
+        self.thread
+            .join()
+            .map_err(|_| AppError::Audio("Capture thread panicked".to_string()))
+    }
+
+    /// Keep everything from now on, after the last `preroll_secs` already captured.
+    fn start_recording(&self) -> Result<(), AppError> {
+        let mut buffer = self.sink.buffer.lock()?;
+        let preroll = preroll_len(self.sink.preroll_secs, self.format);
+        let excess = buffer.len().saturating_sub(preroll);
+        buffer.drain(..excess);
+        *self.sink.progress.lock()? = Progress::default();
+        self.sink.recording.store(true, Ordering::Release);
+        tracing::info!(
+            seconds = buffer.len() as f32 / (self.format.0 as f32 * self.format.1 as f32),
+            "recording started with pre-roll"
+        );
+        Ok(())
+    }
+}
+
+/// Samples in `secs` seconds of interleaved audio, rounded down to whole frames.
+fn preroll_len(secs: f32, (sample_rate, channels): (u32, u16)) -> usize {
+    (secs * sample_rate as f32) as usize * channels as usize
 }
 
+/// Payload of `recording-device-changed` and `recording-interrupted`.
 #[derive(Clone, serde::Serialize)]
-struct AudioLevelEvent {
-    amplitude: f32,
+struct RecordingDeviceEvent {
+    /// The device recording continues on, if any.
+    device_name: Option<String>,
+    message: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct AudioHost {
+    /// Saved in settings and passed back as `host`.
+    id: String,
+    name: String,
+    /// Whether this is the host used when none is chosen.
+    default: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct InputDevice {
+    /// Stable across restarts and locales; saved in settings.
+    pub id: String,
+    /// Display name, numbered when several devices share one.
+    pub name: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct RecordingStarted {
+    device_name: String,
+    /// The requested device was missing and the default device is used instead.
+    fallback: bool,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct RecordingPausedEvent {
+    paused: bool,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct RecordingLimitEvent {
+    /// Seconds left before the recording is stopped.
+    remaining_secs: u32,
+    message: String,
+}
+
+/// The real-time half of capture: the cpal callback converts samples straight into the
+/// ring buffer, without locking or allocating.
+struct RingWriter {
+    producer: rtrb::Producer<f32>,
+    channels: usize,
+    /// Samples dropped because the ring was full.
+    overruns: Arc<AtomicUsize>,
+}
+
+/// The other half: moves samples from the ring into the recording buffer and meters
+/// them, on its own thread.
+struct Drain {
+    consumer: rtrb::Consumer<f32>,
+    /// Adds system audio to the microphone in the mixed source.
+    mixer: Option<Mixer>,
+    sink: CaptureSink,
+    /// Samples kept while not recording.
+    preroll_len: usize,
+    format: (u32, u16),
+    limits: RecordingLimits,
+    meter: Meter,
+    overruns: Arc<AtomicUsize>,
+}
+
+/// Capture settings chosen in the UI. Unset fields use the device default.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct CaptureConfig {
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    /// Frames per callback, clamped to what the device supports.
+    pub buffer_size: Option<u32>,
+    #[serde(default)]
+    pub channel_selection: ChannelSelection,
+    /// `audio-level-update` events per second.
+    pub meter_rate_hz: Option<u32>,
+    #[serde(default)]
+    pub gain: GainSettings,
+    #[serde(default)]
+    pub limits: RecordingLimits,
+    #[serde(default)]
+    pub source: CaptureSource,
+    /// Audio host to use, as named by `get_audio_hosts`; unset for the platform default.
+    pub host: Option<String>,
+}
+
+impl CaptureConfig {
+    /// How the channels written to the buffer mix down. Only the microphone on its own
+    /// writes its channels as they are; the mixed source writes the microphone and system
+    /// audio as one channel each.
+    pub fn buffer_selection(&self) -> ChannelSelection {
+        match self.source {
+            CaptureSource::Microphone => self.channel_selection.clone(),
+            _ => ChannelSelection::Average,
+        }
+    }
+}
+
+/// What a capture records.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureSource {
+    #[default]
+    Microphone,
+    /// What the machine plays, e.g. a video call. `device_id` is an output device; unset
+    /// for the default output.
+    System { device_id: Option<String> },
+    /// Microphone and system audio together, each with its own gain.
+    Mixed {
+        device_id: Option<String>,
+        #[serde(default)]
+        mic_gain_db: f32,
+        #[serde(default)]
+        system_gain_db: f32,
+    },
+}
+
+impl CaptureSource {
+    fn uses_microphone(&self) -> bool {
+        !matches!(self, CaptureSource::System { .. })
+    }
+
+    /// The output device to record system audio from: `Some(None)` for the default
+    /// output, `None` if the source has no system audio.
+    fn system_device_id(&self) -> Option<Option<&str>> {
+        match self {
+            CaptureSource::Microphone => None,
+            CaptureSource::System { device_id } | CaptureSource::Mixed { device_id, .. } => {
+                Some(device_id.as_deref())
+            }
+        }
+    }
+}
+
+/// Bounds on one recording, so a forgotten shortcut can't fill up memory.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct RecordingLimits {
+    pub max_duration_secs: Option<u32>,
+    /// Size of the recording buffer; not applied while spilling to disk.
+    pub max_memory_mb: Option<u32>,
+    /// Write the recording to disk as it is captured instead of keeping it in memory.
+    #[serde(default)]
+    pub spill_to_disk: bool,
+}
+
+impl RecordingLimits {
+    /// Samples a recording may hold in `format`, in whole frames, or `None` if unlimited.
+    fn max_samples(&self, (sample_rate, channels): (u32, u16)) -> Option<usize> {
+        let frame = channels as usize;
+        let duration = self
+            .max_duration_secs
+            .map(|secs| secs as usize * sample_rate as usize * frame);
+        let memory = self
+            .max_memory_mb
+            .filter(|_| !self.spill_to_disk)
+            .map(|mb| mb as usize * 1024 * 1024 / size_of::<f32>() / frame * frame);
+        match (duration, memory) {
+            (Some(duration), Some(memory)) => Some(duration.min(memory)),
+            (duration, memory) => duration.or(memory),
+        }
+    }
+}
+
+/// One range of configurations a device can capture with.
+#[derive(serde::Serialize)]
+pub struct InputConfigRange {
+    channels: u16,
+    min_sample_rate: u32,
+    max_sample_rate: u32,
+    sample_format: String,
+    min_buffer_size: Option<u32>,
+    max_buffer_size: Option<u32>,
+}
+
+#[derive(serde::Serialize)]
+pub struct InputConfigs {
+    default_sample_rate: u32,
+    default_channels: u16,
+    configs: Vec<InputConfigRange>,
+}
+
+/// cpal hosts, each built on first use and then kept. Building the JACK host registers
+/// a JACK client per direction, so building it for every device list would leave clients
+/// behind and hand out new names.
+#[derive(Default)]
+struct Hosts(Mutex<Vec<Arc<cpal::Host>>>);
+
+impl Hosts {
+    /// The host with the given ID, or the platform default when `id` is `None`.
+    fn get(&self, id: Option<&str>) -> Result<Arc<cpal::Host>, AppError> {
+        let unavailable =
+            |id: &str| AppError::Audio(format!("Audio host '{}' is not available", id));
+        let id = match id {
+            None => cpal::default_host().id(),
+            Some(id) => id.parse().map_err(|_| unavailable(id))?,
+        };
+        let mut hosts = self.0.lock()?;
+        if let Some(host) = hosts.iter().find(|h| h.id() == id) {
+            return Ok(host.clone());
+        }
+        let host = Arc::new(cpal::host_from_id(id).map_err(|_| unavailable(&id.to_string()))?);
+        hosts.push(host.clone());
+        Ok(host)
+    }
+
+    /// The host a device ID names, or `host` for IDs without one. A device is looked up
+    /// on its own host, so a saved device keeps working after the host setting changes.
+    fn for_device(&self, host: &Arc<cpal::Host>, id: &str) -> Result<Arc<cpal::Host>, AppError> {
+        // Names saved by older versions don't start with a host.
+        let named = id
+            .split_once(':')
+            .filter(|(prefix, _)| prefix.parse::<cpal::HostId>().is_ok_and(|h| h != host.id()));
+        match named {
+            Some((prefix, _)) => self
+                .get(Some(prefix))
+                .map_err(|_| AppError::DeviceNotFound(id.to_string())),
+            None => Ok(host.clone()),
+        }
+    }
+}
+
+/// cpal's JACK host builds its devices once, when the host is built.
+fn is_jack(host: cpal::HostId) -> bool {
+    host.to_string() == "jack"
+}
+
+fn host_name(id: cpal::HostId) -> &'static str {
+    match id.to_string().as_str() {
+        "alsa" => "ALSA",
+        "jack" => "JACK",
+        "wasapi" => "WASAPI",
+        "asio" => "ASIO",
+        "coreaudio" => "Core Audio",
+        _ => id.name(),
+    }
+}
+
+fn list_input_devices(host: &cpal::Host) -> Result<Vec<InputDevice>, AppError> {
+    let devices = host.input_devices().map_err(AppError::audio)?;
+    Ok(name_devices(host.id(), devices))
+}
+
+/// Input devices of the host the frontend last listed.
+pub fn input_devices(state: &AudioState) -> Result<Vec<InputDevice>, AppError> {
+    let host = state.listed_host()?;
+    list_input_devices(&host)
+}
+
+fn name_devices(
+    host: cpal::HostId,
+    devices: impl Iterator<Item = cpal::Device>,
+) -> Vec<InputDevice> {
+    let mut list = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+    for (id, device) in with_ids(host, devices) {
+        let name = display_name(&device);
+        // Two identical USB headsets share a name; number the later ones.
+        let same = names.iter().filter(|n| **n == name).count();
+        names.push(name.clone());
+        list.push(InputDevice {
+            id,
+            name: if same == 0 {
+                name
+            } else {
+                format!("{} ({})", name, same + 1)
+            },
+        });
+    }
+    list
+}
+
+/// Audio hosts available on this machine, e.g. ALSA and JACK on Linux. PipeWire is
+/// reached through either, via its ALSA plugin or its JACK server.
+#[tauri::command]
+pub fn get_audio_hosts() -> Vec<AudioHost> {
+    let default = cpal::default_host().id();
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| AudioHost {
+            id: id.to_string(),
+            name: host_name(id).to_string(),
+            default: id == default,
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_input_devices(
+    state: State<AudioState>,
+    host: Option<String>,
+) -> Result<Vec<InputDevice>, AppError> {
+    let devices = list_input_devices(&*state.hosts.get(host.as_deref())?)?;
+    *state.device_host.lock()? = host;
+    Ok(devices)
+}
+
+/// Output devices whose playback can be recorded as system audio.
+#[tauri::command]
+pub fn get_output_devices(
+    state: State<AudioState>,
+    host: Option<String>,
+) -> Result<Vec<InputDevice>, AppError> {
+    system::list_output_devices(&*state.hosts.get(host.as_deref())?)
 }
 
+/// Poll for plugged in and removed input devices only while the frontend shows them.
 #[tauri::command]
-pub fn get_input_devices() -> Result<Vec<String>, String> {
-    let host = cpal::default_host();
-    let devices = host.input_devices().map_err(|e| e.to_string())?;
-    let mut device_names = Vec::new();
-    for device in devices {
-        if let Ok(desc) = device.description() {
-            device_names.push(format_device_name(&desc));
+pub fn set_device_watch(state: State<AudioState>, enabled: bool) {
+    state.watch_devices.store(enabled, Ordering::Relaxed);
+}
+
+/// Emit `input-devices-changed` with the new list whenever an input device is plugged in
+/// or removed, while `set_device_watch` has enabled it.
+pub fn watch_input_devices(app: AppHandle) {
+    std::thread::spawn(move || {
+        let state = app.state::<AudioState>();
+        let mut known = input_devices(&state).unwrap_or_default();
+        loop {
+            std::thread::sleep(DEVICE_POLL_INTERVAL);
+            if !state.watch_devices.load(Ordering::Relaxed) {
+                continue;
+            }
+            let Ok(host) = state.listed_host() else {
+                continue;
+            };
+            // Nothing to poll: JACK's devices don't change after the host is built.
+            if is_jack(host.id()) {
+                continue;
+            }
+            let Ok(devices) = list_input_devices(&host) else {
+                continue;
+            };
+            if devices != known {
+                tracing::info!(count = devices.len(), "input devices changed");
+                let _ = app.emit("input-devices-changed", &devices);
+                known = devices;
+            }
         }
+    });
+}
+
+/// Devices paired with their IDs, in enumeration order. Devices without an ID of their
+/// own get the host and display name, numbered from the second of several identical
+/// devices so that each ID still picks a single device.
+fn with_ids(
+    host: cpal::HostId,
+    devices: impl Iterator<Item = cpal::Device>,
+) -> Vec<(String, cpal::Device)> {
+    let mut fallbacks = Vec::new();
+    devices
+        .map(|device| {
+            let id = device_id(host, &device).unwrap_or_else(|| {
+                numbered(
+                    format!("{}:{}", host, display_name(&device)),
+                    &mut fallbacks,
+                )
+            });
+            (id, device)
+        })
+        .collect()
+}
+
+/// `id`, or `id#2`, `id#3` and so on when it was handed out before.
+fn numbered(id: String, taken: &mut Vec<String>) -> String {
+    let same = taken.iter().filter(|t| **t == id).count();
+    taken.push(id.clone());
+    if same == 0 {
+        id
+    } else {
+        format!("{}#{}", id, same + 1)
+    }
+}
+
+/// cpal's host-qualified device ID, if the backend has one.
+fn device_id(host: cpal::HostId, device: &cpal::Device) -> Option<String> {
+    if is_jack(host) {
+        return Some(jack_device_id(device.supports_input()));
     }
-    Ok(device_names)
+    device.id().ok().map(|id| id.to_string())
+}
+
+/// cpal's JACK host has one input and one output device, and their IDs are the names
+/// of their JACK clients. JACK renames a client whose name is taken, e.g. to
+/// `cpal_client_in-01`, so the direction identifies the device instead.
+fn jack_device_id(input: bool) -> String {
+    format!("jack:{}", if input { "input" } else { "output" })
+}
+
+fn display_name(device: &cpal::Device) -> String {
+    device
+        .description()
+        .map(|d| format_device_name(&d))
+        .unwrap_or_else(|_| "Unknown Device".to_string())
 }
 
 /// Format device name with extended info if available
@@ -53,179 +616,1017 @@ fn format_device_name(desc: &cpal::device_description::DeviceDescription) -> Str
     }
 }
 
+/// The input device with the given ID, or the host's default when `id` is `None`.
+fn find_device(
+    hosts: &Hosts,
+    host: &Arc<cpal::Host>,
+    id: Option<&str>,
+) -> Result<cpal::Device, AppError> {
+    let Some(id) = id else {
+        return host.default_input_device().ok_or(AppError::NoInputDevice);
+    };
+    let host = hosts.for_device(host, id)?;
+    let devices = host.input_devices().map_err(AppError::audio)?;
+    with_ids(host.id(), devices)
+        .into_iter()
+        // Settings from older versions saved the display name instead of the ID.
+        .find(|(device_id, device)| device_id == id || display_name(device) == id)
+        .map(|(_, device)| device)
+        .ok_or_else(|| AppError::DeviceNotFound(id.to_string()))
+}
+
+#[tauri::command]
+pub fn get_input_configs(
+    state: State<AudioState>,
+    host: Option<String>,
+    device_id: Option<String>,
+) -> Result<InputConfigs, AppError> {
+    let host = state.hosts.get(host.as_deref())?;
+    let device = find_device(&state.hosts, &host, device_id.as_deref())?;
+    let default = device.default_input_config().map_err(AppError::audio)?;
+    let configs = device
+        .supported_input_configs()
+        .map_err(AppError::audio)?
+        .map(|c| {
+            let (min_buffer_size, max_buffer_size) = match c.buffer_size() {
+                SupportedBufferSize::Range { min, max } => (Some(*min), Some(*max)),
+                SupportedBufferSize::Unknown => (None, None),
+            };
+            InputConfigRange {
+                channels: c.channels(),
+                min_sample_rate: c.min_sample_rate(),
+                max_sample_rate: c.max_sample_rate(),
+                sample_format: c.sample_format().to_string(),
+                min_buffer_size,
+                max_buffer_size,
+            }
+        })
+        .collect();
+    Ok(InputConfigs {
+        default_sample_rate: default.sample_rate(),
+        default_channels: default.channels(),
+        configs,
+    })
+}
+
+/// Pick the device configuration matching `capture`, preferring float and then wider
+/// sample formats. Falls back to the device default if nothing matches, e.g. after the
+/// device changed since the settings were saved.
+fn select_config(
+    device: &cpal::Device,
+    capture: &CaptureConfig,
+) -> Result<(cpal::SupportedStreamConfig, cpal::StreamConfig), AppError> {
+    let default = device.default_input_config().map_err(AppError::audio)?;
+    let sample_rate = capture.sample_rate.unwrap_or(default.sample_rate());
+    let channels = capture.channels.unwrap_or(default.channels());
+
+    let wants_default = (sample_rate, channels) == (default.sample_rate(), default.channels());
+    let matching = if wants_default {
+        None
+    } else {
+        device
+            .supported_input_configs()
+            .map_err(AppError::audio)?
+            .filter(|c| c.channels() == channels)
+            .filter_map(|c| c.try_with_sample_rate(sample_rate))
+            .max_by_key(|c| {
+                (
+                    c.sample_format().is_float(),
+                    c.sample_format().sample_size(),
+                )
+            })
+    };
+    let config = matching.unwrap_or_else(|| {
+        if !wants_default {
+            tracing::warn!(
+                sample_rate,
+                channels,
+                "capture config not supported, using device default"
+            );
+        }
+        default
+    });
+
+    let mut stream_config = config.config();
+    if let Some(frames) = capture.buffer_size {
+        stream_config.buffer_size = cpal::BufferSize::Fixed(match config.buffer_size() {
+            SupportedBufferSize::Range { min, max } => frames.clamp(*min, *max),
+            SupportedBufferSize::Unknown => frames,
+        });
+    }
+    Ok((config, stream_config))
+}
+
 #[tauri::command]
 pub fn start_recording(
     window: WebviewWindow,
     state: State<AudioState>,
-    device_name: Option<String>,
-) -> Result<String, String> {
-    let mut is_recording = state.is_recording.lock().map_err(|e| e.to_string())?;
+    device_id: Option<String>,
+    capture: Option<CaptureConfig>,
+) -> Result<RecordingStarted, AppError> {
+    let mut is_recording = state.is_recording.lock()?;
     if *is_recording {
-        return Err("Already recording".to_string());
+        return Err(AppError::AlreadyRecording);
     }
 
-    let (tx, rx) = mpsc::channel();
-    *state.stop_tx.lock().map_err(|e| e.to_string())? = Some(tx);
+    let request = CaptureRequest {
+        device_id,
+        config: capture.unwrap_or_default(),
+    };
+    // Not locked while a warm capture is stopped or a new one opened; `is_recording`
+    // keeps other recordings out meanwhile.
+    let current = state.capture.lock()?.take();
+    let capture = match current {
+        // Already open on the right device: keep its pre-roll.
+        Some(warm) if warm.request == request => {
+            warm.start_recording()?;
+            warm
+        }
+        other => {
+            if let Some(warm) = other {
+                warm.stop()?;
+            }
+            // Clear previous recording
+            state.recording_buffer.lock()?.clear();
+            open_capture(request, &state.recording_buffer, &window, None)?
+        }
+    };
 
-    let buffer = state.recording_buffer.clone();
-    // Clear previous recording
-    {
-        let mut buf = buffer.lock().map_err(|e| e.to_string())?;
-        buf.clear();
+    *state.format.lock()? = Some(capture.format);
+    *state.channel_selection.lock()? = capture.request.config.buffer_selection();
+    *state.gain.lock()? = capture.request.config.gain.clone();
+    let started = RecordingStarted {
+        device_name: capture.device_name.clone(),
+        fallback: capture.fallback,
+    };
+    *state.capture.lock()? = Some(capture);
+    *is_recording = true;
+
+    drop(is_recording);
+    let _ = crate::tray::refresh_menu(window.app_handle());
+    Ok(started)
+}
+
+/// Keep the microphone open between recordings, so a recording starts with the last
+/// `preroll_secs` seconds before the shortcut was pressed. The request must match the
+/// one `start_recording` gets for the pre-roll to be used.
+#[tauri::command]
+pub fn set_warm_capture(
+    window: WebviewWindow,
+    state: State<AudioState>,
+    enabled: bool,
+    device_id: Option<String>,
+    capture: Option<CaptureConfig>,
+    preroll_secs: f32,
+) -> Result<(), AppError> {
+    let is_recording = state.is_recording.lock()?;
+    *state.warm.lock()? = enabled.then(|| WarmCapture {
+        request: CaptureRequest {
+            device_id,
+            config: capture.unwrap_or_default(),
+        },
+        preroll_secs: preroll_secs.clamp(0.0, MAX_PREROLL_SECS),
+    });
+    // A running recording keeps its stream; this applies once it stops.
+    if *is_recording {
+        return Ok(());
     }
 
-    let host = cpal::default_host();
+    // Taken out first so the capture lock isn't held while the thread is joined.
+    let capture = state.capture.lock()?.take();
+    if let Some(capture) = capture {
+        capture.stop()?;
+    }
+    let result = rewarm(&window, &state);
+    drop(is_recording);
+    let _ = crate::tray::refresh_menu(window.app_handle());
+    result
+}
 
-    let device = if let Some(name) = device_name {
-        host.input_devices()
-            .map_err(|e| e.to_string())?
-            .find(|x| {
-                x.description()
-                    .map(|d| format_device_name(&d) == name)
-                    .unwrap_or(false)
-            })
-            .ok_or(format!("Device '{}' not found", name))?
+/// Open the warm capture, if enabled, after a recording or a settings change.
+fn rewarm(window: &WebviewWindow, state: &AudioState) -> Result<(), AppError> {
+    let Some(warm) = state.warm.lock()?.clone() else {
+        return Ok(());
+    };
+    state.recording_buffer.lock()?.clear();
+    let capture = open_capture(
+        warm.request,
+        &state.recording_buffer,
+        window,
+        Some(warm.preroll_secs),
+    )?;
+    *state.capture.lock()? = Some(capture);
+    Ok(())
+}
+
+/// Start capturing from the requested device, or the default one if it is missing.
+/// With `preroll_secs` set, only that much audio is kept until `start_recording`.
+fn open_capture(
+    request: CaptureRequest,
+    buffer: &Arc<Mutex<Vec<f32>>>,
+    window: &WebviewWindow,
+    preroll_secs: Option<f32>,
+) -> Result<Capture, AppError> {
+    let state = window.state::<AudioState>();
+    let hosts = &state.hosts;
+    let host = hosts.get(request.config.host.as_deref())?;
+    let mut fallback = false;
+    let mic = if request.config.source.uses_microphone() {
+        Some(
+            match find_device(hosts, &host, request.device_id.as_deref()) {
+                Err(AppError::DeviceNotFound(id)) => {
+                    tracing::warn!(device = %id, "input device missing, using the default device");
+                    fallback = true;
+                    find_device(hosts, &host, None)?
+                }
+                device => device?,
+            },
+        )
     } else {
-        host.default_input_device()
-            .ok_or("No input device available")?
+        None
+    };
+    let system = match request.config.source.system_device_id() {
+        None => None,
+        Some(id) => Some(match system::find(hosts, &host, id) {
+            Err(AppError::DeviceNotFound(id)) => {
+                tracing::warn!(device = %id, "output device missing, using the default output");
+                fallback = true;
+                system::find(hosts, &host, None)?
+            }
+            device => device?,
+        }),
     };
 
-    let selected_device_name = device
-        .description()
-        .map(|d| format_device_name(&d))
-        .unwrap_or_else(|_| "Unknown Device".to_string());
-    let config = device.default_input_config().map_err(|e| e.to_string())?;
+    let (control_tx, control_rx) = mpsc::channel();
+    let sink = CaptureSink {
+        buffer: buffer.clone(),
+        window: window.clone(),
+        control_tx,
+        recording: Arc::new(AtomicBool::new(preroll_secs.is_none())),
+        paused: Arc::new(AtomicBool::new(false)),
+        preroll_secs: preroll_secs.unwrap_or(0.0),
+        progress: Arc::new(Mutex::new(Progress::default())),
+    };
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let thread = {
+        let sink = sink.clone();
+        let config = request.config.clone();
+        std::thread::spawn(move || {
+            let (mut current, format) =
+                match open_stream(mic.as_ref(), system.as_ref(), &config, &sink) {
+                    Ok(open) => {
+                        let _ = ready_tx.send(Ok((open.device_name.clone(), open.format)));
+                        let format = open.format;
+                        (Some(open), format)
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(e));
+                        return;
+                    }
+                };
 
-    // We need to clone things to move into thread
-    let buffer_clone = buffer.clone();
-    let window_clone = window.clone();
+            // Wait for stop signal, moving to the default device if ours is unplugged so
+            // the recording so far isn't lost.
+            loop {
+                match control_rx.recv() {
+                    Ok(CaptureEvent::DeviceLost) => {
+                        let Some(open) = current.take() else {
+                            continue;
+                        };
+                        open.close();
+                        // The old stream may have reported the loss more than once.
+                        while let Ok(event) = control_rx.try_recv() {
+                            if let CaptureEvent::Stop = event {
+                                return;
+                            }
+                        }
+                        current = switch_to_default(format, &config, &sink);
+                    }
+                    Ok(CaptureEvent::SystemLost(reason)) => {
+                        if current.is_none() {
+                            continue;
+                        }
+                        tracing::error!("system audio lost: {}", reason);
+                        // The mixer records silence in its place, so the microphone goes on.
+                        if matches!(config.source, CaptureSource::Mixed { .. }) {
+                            if sink.recording.load(Ordering::Acquire) {
+                                let _ = sink.window.emit(
+                                    "recording-device-changed",
+                                    RecordingDeviceEvent {
+                                        message: format!(
+                                            "System audio lost, continuing with the microphone only: {}",
+                                            reason
+                                        ),
+                                        device_name: current.as_ref().map(|o| o.device_name.clone()),
+                                    },
+                                );
+                            }
+                            continue;
+                        }
+                        if let Some(open) = current.take() {
+                            open.close();
+                        }
+                        interrupt(&sink, format!("System audio lost: {}", reason));
+                    }
+                    Ok(CaptureEvent::Stop) | Err(_) => break,
+                }
+            }
+            if let Some(open) = current {
+                open.close();
+            }
+        })
+    };
 
-    let stream_config: cpal::StreamConfig = config.clone().into();
+    // Report build and play failures to the caller instead of losing them in the thread.
+    let ready = ready_rx
+        .recv()
+        .unwrap_or_else(|_| Err(AppError::Audio("Capture thread exited".to_string())));
+    match ready {
+        Ok((device_name, format)) => Ok(Capture {
+            sink,
+            thread,
+            request,
+            device_name,
+            format,
+            fallback,
+        }),
+        Err(e) => {
+            let _ = thread.join();
+            tracing::error!("failed to start capture: {}", e);
+            Err(e)
+        }
+    }
+}
 
-    // Save format now
-    *state.format.lock().map_err(|e| e.to_string())? =
-        Some((stream_config.sample_rate, stream_config.channels));
+/// The playing inputs of a capture and what they capture.
+struct OpenStream {
+    inputs: Vec<Input>,
+    drain: JoinHandle<()>,
+    device_name: String,
+    /// Sample rate and channel count of the samples written to the buffer.
+    format: (u32, u16),
+}
 
-    std::thread::spawn(move || {
-        let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
+impl OpenStream {
+    /// Stop the stream and wait until everything it captured is in the buffer.
+    fn close(self) {
+        // Dropping the inputs drops the ring's producer, which ends the drain thread.
+        self.inputs.into_iter().for_each(Input::close);
+        let _ = self.drain.join();
+    }
+}
 
-        let stream = match config.sample_format() {
-            cpal::SampleFormat::F32 => device.build_input_stream(
-                &stream_config,
-                move |data: &[f32], _: &_| {
-                    write_input_data(data, &buffer_clone, &window_clone);
-                },
-                err_fn,
-                None,
-            ),
-            cpal::SampleFormat::I16 => device.build_input_stream(
-                &stream_config,
-                move |data: &[i16], _: &_| {
-                    let f32_data: Vec<f32> =
-                        data.iter().map(|&x| x as f32 / i16::MAX as f32).collect();
-                    write_input_data(&f32_data, &buffer_clone, &window_clone);
+/// Keeps samples coming into a ring buffer until closed.
+enum Input {
+    Stream(cpal::Stream),
+    #[cfg(target_os = "linux")]
+    Monitor(system::MonitorReader),
+}
+
+impl Input {
+    fn close(self) {
+        match self {
+            Input::Stream(stream) => drop(stream),
+            #[cfg(target_os = "linux")]
+            Input::Monitor(reader) => drop(reader),
+        }
+    }
+}
+
+/// One input of a capture, before it is connected to the drain.
+struct OpenInput {
+    input: Input,
+    consumer: rtrb::Consumer<f32>,
+    format: (u32, u16),
+    name: String,
+}
+
+/// A ring buffer for `RING_SECONDS` of audio in `format`. A whole number of frames, so
+/// wrapping around never splits a frame.
+fn ring(format: (u32, u16), overruns: &Arc<AtomicUsize>) -> (RingWriter, rtrb::Consumer<f32>) {
+    let channels = format.1 as usize;
+    let (producer, consumer) = rtrb::RingBuffer::new(format.0 as usize * channels * RING_SECONDS);
+    let writer = RingWriter {
+        producer,
+        channels,
+        overruns: overruns.clone(),
+    };
+    (writer, consumer)
+}
+
+fn open_stream(
+    mic: Option<&cpal::Device>,
+    system: Option<&SystemDevice>,
+    capture: &CaptureConfig,
+    sink: &CaptureSink,
+) -> Result<OpenStream, AppError> {
+    let overruns = Arc::new(AtomicUsize::new(0));
+    let mic = mic
+        .map(|device| open_microphone(device, capture, sink, &overruns))
+        .transpose()?;
+    let system = system
+        .map(|device| system::open(device, sink, &overruns))
+        .transpose()?;
+
+    let (inputs, consumer, mixer, device_name, format) = match (mic, system) {
+        (Some(mic), None) => (vec![mic.input], mic.consumer, None, mic.name, mic.format),
+        (None, Some(system)) => (
+            vec![system.input],
+            system.consumer,
+            None,
+            system.name,
+            system.format,
+        ),
+        (Some(mic), Some(system)) => {
+            let (mic_gain_db, system_gain_db) = match capture.source {
+                CaptureSource::Mixed {
+                    mic_gain_db,
+                    system_gain_db,
+                    ..
+                } => (mic_gain_db, system_gain_db),
+                _ => (0.0, 0.0),
+            };
+            let mixer = Mixer::new(
+                mic.format,
+                capture.channel_selection.clone(),
+                mic_gain_db,
+                system.consumer,
+                system.format,
+                system_gain_db,
+            );
+            (
+                vec![mic.input, system.input],
+                mic.consumer,
+                Some(mixer),
+                format!("{} + {}", mic.name, system.name),
+                (mic.format.0, 2),
+            )
+        }
+        (None, None) => return Err(AppError::NoInputDevice),
+    };
+
+    let drain = Drain {
+        consumer,
+        mixer,
+        sink: sink.clone(),
+        preroll_len: preroll_len(sink.preroll_secs, format),
+        format,
+        limits: capture.limits.clone(),
+        meter: Meter::new(
+            format.0,
+            format.1 as usize,
+            capture.buffer_selection(),
+            &capture.gain,
+            capture.meter_rate_hz.unwrap_or(meter::DEFAULT_RATE_HZ),
+        ),
+        overruns,
+    };
+    let drain = std::thread::spawn(move || drain.run());
+
+    Ok(OpenStream {
+        inputs,
+        drain,
+        device_name,
+        format,
+    })
+}
+
+fn open_microphone(
+    device: &cpal::Device,
+    capture: &CaptureConfig,
+    sink: &CaptureSink,
+    overruns: &Arc<AtomicUsize>,
+) -> Result<OpenInput, AppError> {
+    let name = display_name(device);
+    let (config, stream_config) = select_config(device, capture)?;
+    let format = (stream_config.sample_rate, stream_config.channels);
+
+    let selection = &capture.channel_selection;
+    if !selection.fits(format.1 as usize) {
+        tracing::warn!(
+            ?selection,
+            channels = format.1,
+            "channel selection out of range, averaging"
+        );
+    }
+    let (writer, consumer) = ring(format, overruns);
+    let stream = build_stream(
+        device,
+        &stream_config,
+        config.sample_format(),
+        writer,
+        LossReport {
+            control_tx: sink.control_tx.clone(),
+            event: CaptureEvent::DeviceLost,
+        },
+    )?;
+    stream.play().map_err(AppError::audio)?;
+
+    tracing::info!(
+        device = %name,
+        sample_rate = format.0,
+        channels = format.1,
+        format = %config.sample_format(),
+        buffer_size = ?stream_config.buffer_size,
+        "input stream opened"
+    );
+    Ok(OpenInput {
+        input: Input::Stream(stream),
+        consumer,
+        format,
+        name,
+    })
+}
+
+/// Reopen the capture on the default devices after its microphone went away, telling the
+/// frontend either way.
+fn switch_to_default(
+    format: (u32, u16),
+    config: &CaptureConfig,
+    sink: &CaptureSink,
+) -> Option<OpenStream> {
+    match reopen_on_default(format, config, sink) {
+        Ok(open) => {
+            tracing::warn!(device = %open.device_name, "input device lost, switched");
+            // Nobody needs to hear about it while only the pre-roll is kept.
+            if sink.recording.load(Ordering::Acquire) {
+                let _ = sink.window.emit(
+                    "recording-device-changed",
+                    RecordingDeviceEvent {
+                        message: format!(
+                            "Audio device disconnected, continuing with {}",
+                            open.device_name
+                        ),
+                        device_name: Some(open.device_name.clone()),
+                    },
+                );
+            }
+            Some(open)
+        }
+        Err(e) => {
+            tracing::error!("input device lost: {}", e);
+            interrupt(sink, format!("Audio device disconnected: {}", e));
+            None
+        }
+    }
+}
+
+/// Tell the frontend the recording can't go on, so it transcribes what it has.
+fn interrupt(sink: &CaptureSink, message: String) {
+    if sink.recording.load(Ordering::Acquire) {
+        let _ = sink.window.emit(
+            "recording-interrupted",
+            RecordingDeviceEvent {
+                device_name: None,
+                message,
+            },
+        );
+    }
+}
+
+/// Continue a recording on the default devices. The buffer holds interleaved samples in
+/// `format`, so the new streams have to deliver exactly that.
+fn reopen_on_default(
+    format: (u32, u16),
+    capture: &CaptureConfig,
+    sink: &CaptureSink,
+) -> Result<OpenStream, AppError> {
+    let state = sink.window.state::<AudioState>();
+    let hosts = &state.hosts;
+    let host = hosts.get(capture.host.as_deref())?;
+    let mic = if capture.source.uses_microphone() {
+        Some(find_device(hosts, &host, None)?)
+    } else {
+        None
+    };
+    let system = match capture.source.system_device_id() {
+        Some(_) => Some(system::find(hosts, &host, None)?),
+        None => None,
+    };
+    let capture = CaptureConfig {
+        sample_rate: Some(format.0),
+        // The mixed source writes two channels whatever the microphone has.
+        channels: match capture.source {
+            CaptureSource::Microphone => Some(format.1),
+            _ => capture.channels,
+        },
+        ..capture.clone()
+    };
+
+    // Checked before opening anything, so no samples in another format reach the buffer.
+    let name = mic
+        .as_ref()
+        .map_or_else(|| "System audio".to_string(), display_name);
+    if stream_format(mic.as_ref(), system.as_ref(), &capture)? != format {
+        return Err(AppError::Audio(format!(
+            "{} can't record at {} Hz with {} channels",
+            name, format.0, format.1
+        )));
+    }
+    open_stream(mic.as_ref(), system.as_ref(), &capture, sink)
+}
+
+/// The format `open_stream` writes to the buffer with these inputs.
+fn stream_format(
+    mic: Option<&cpal::Device>,
+    system: Option<&SystemDevice>,
+    capture: &CaptureConfig,
+) -> Result<(u32, u16), AppError> {
+    // select_config falls back to the device default rather than failing.
+    let mic = mic
+        .map(|device| select_config(device, capture))
+        .transpose()?
+        .map(|(_, config)| (config.sample_rate, config.channels));
+    match (mic, system) {
+        (Some(mic), None) => Ok(mic),
+        (Some((sample_rate, _)), Some(_)) => Ok((sample_rate, 2)),
+        (None, Some(system)) => system::format(system),
+        (None, None) => Err(AppError::NoInputDevice),
+    }
+}
+
+fn build_stream(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sample_format: SampleFormat,
+    writer: RingWriter,
+    lost: LossReport,
+) -> Result<cpal::Stream, AppError> {
+    match sample_format {
+        SampleFormat::I8 => build_stream_as::<i8>(device, config, writer, lost),
+        SampleFormat::I16 => build_stream_as::<i16>(device, config, writer, lost),
+        SampleFormat::I24 => build_stream_as::<cpal::I24>(device, config, writer, lost),
+        SampleFormat::I32 => build_stream_as::<i32>(device, config, writer, lost),
+        SampleFormat::I64 => build_stream_as::<i64>(device, config, writer, lost),
+        SampleFormat::U8 => build_stream_as::<u8>(device, config, writer, lost),
+        SampleFormat::U16 => build_stream_as::<u16>(device, config, writer, lost),
+        SampleFormat::U24 => build_stream_as::<cpal::U24>(device, config, writer, lost),
+        SampleFormat::U32 => build_stream_as::<u32>(device, config, writer, lost),
+        SampleFormat::U64 => build_stream_as::<u64>(device, config, writer, lost),
+        SampleFormat::F32 => build_stream_as::<f32>(device, config, writer, lost),
+        SampleFormat::F64 => build_stream_as::<f64>(device, config, writer, lost),
+        format => {
+            return Err(AppError::Audio(format!(
+                "Unsupported sample format: {}",
+                format
+            )));
+        }
+    }
+    .map_err(AppError::audio)
+}
+
+/// Input stream delivering `T` samples, converted to `f32` in `-1.0..=1.0`.
+fn build_stream_as<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    writer: RingWriter,
+    lost: LossReport,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let mut callback = data_callback(writer);
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &_| callback(data),
+        move |err| {
+            tracing::error!("an error occurred on stream: {}", err);
+            if let cpal::StreamError::DeviceNotAvailable = err {
+                lost.send();
+            }
+        },
+        None,
+    )
+}
+
+/// The stream's data callback. It runs on the audio thread, so it must not allocate or
+/// take a lock.
+fn data_callback<T>(mut writer: RingWriter) -> impl FnMut(&[T]) + Send + 'static
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    move |data| writer.write(data)
+}
+
+impl RingWriter {
+    fn write<T>(&mut self, data: &[T])
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let n = data.len().min(self.producer.slots());
+        // Whole frames only, so the drain never sees a partial one.
+        let n = n - n % self.channels;
+        if let Ok(chunk) = self.producer.write_chunk_uninit(n) {
+            chunk.fill_from_iter(data.iter().map(|&x| x.to_sample::<f32>()));
+        }
+        if n < data.len() {
+            self.overruns.fetch_add(data.len() - n, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drain {
+    /// Runs until the stream, and with it the producer, is dropped and the ring is empty.
+    fn run(mut self) {
+        loop {
+            // Checked before reading, so samples written just before the drop still count.
+            let abandoned = self.consumer.is_abandoned();
+            let available = self.consumer.slots();
+            if available == 0 {
+                if abandoned {
+                    break;
+                }
+                std::thread::sleep(DRAIN_INTERVAL);
+                continue;
+            }
+
+            let Ok(chunk) = self.consumer.read_chunk(available) else {
+                continue;
+            };
+            let (first, second) = chunk.as_slices();
+            let mixed;
+            let (first, second) = match &mut self.mixer {
+                Some(mixer) => {
+                    mixed = mixer.mix([first, second]);
+                    (&mixed[..], &[][..])
+                }
+                None => (first, second),
+            };
+            let recording = self.sink.recording.load(Ordering::Acquire);
+            // Paused samples are read to keep the ring moving, then dropped.
+            let paused = self.sink.paused.load(Ordering::Acquire);
+            if !paused && let Ok(mut buf) = self.sink.buffer.lock() {
+                if recording {
+                    record(
+                        &self.sink,
+                        &self.limits,
+                        self.format,
+                        &mut buf,
+                        [first, second],
+                    );
+                } else {
+                    buf.extend_from_slice(first);
+                    buf.extend_from_slice(second);
+                    // While warm, drop all but the pre-roll; in batches, to move memory rarely.
+                    if buf.len() > 2 * self.preroll_len {
+                        let excess = buf.len() - self.preroll_len;
+                        buf.drain(..excess);
+                    }
+                }
+            }
+            for part in [first, second] {
+                if let Some(level) = self.meter.process(part)
+                    && recording
+                    && !paused
+                {
+                    let _ = self.sink.window.emit("audio-level-update", level);
+                }
+            }
+            chunk.commit_all();
+        }
+
+        let overruns = self.overruns.load(Ordering::Relaxed);
+        if overruns > 0 {
+            tracing::warn!(samples = overruns, "capture ring buffer overran");
+        }
+    }
+}
+
+/// Append to the recording up to its limit, spilling to disk if enabled.
+fn record(
+    sink: &CaptureSink,
+    limits: &RecordingLimits,
+    format: (u32, u16),
+    buf: &mut Vec<f32>,
+    parts: [&[f32]; 2],
+) {
+    let Ok(mut progress) = sink.progress.lock() else {
+        return;
+    };
+    match append(&mut progress, limits, format, buf, parts) {
+        Limit::Within => {}
+        Limit::Near(remaining_secs) => {
+            let _ = sink.window.emit(
+                "recording-limit-warning",
+                RecordingLimitEvent {
+                    remaining_secs,
+                    message: format!("Recording stops in {} seconds", remaining_secs),
                 },
-                err_fn,
-                None,
-            ),
-            cpal::SampleFormat::U16 => device.build_input_stream(
-                &stream_config,
-                move |data: &[u16], _: &_| {
-                    let f32_data: Vec<f32> = data
-                        .iter()
-                        .map(|&x| (x as f32 - u16::MAX as f32 / 2.0) / (u16::MAX as f32 / 2.0))
-                        .collect();
-                    write_input_data(&f32_data, &buffer_clone, &window_clone);
+            );
+        }
+        Limit::Reached => {
+            tracing::warn!(samples = ?limits.max_samples(format), "recording limit reached");
+            let _ = sink.window.emit(
+                "recording-limit-reached",
+                RecordingLimitEvent {
+                    remaining_secs: 0,
+                    message: "Maximum recording length reached, transcribing".to_string(),
                 },
-                err_fn,
-                None,
-            ),
-            _ => return, // Should handle error but difficult in thread
+            );
         }
-        .expect("Failed to build stream");
+    }
+}
 
-        stream.play().expect("Failed to play stream");
+/// Where a recording stands against its limits, when there is news to report.
+#[derive(Debug, PartialEq)]
+enum Limit {
+    Within,
+    /// This many seconds are left; reported once.
+    Near(u32),
+    /// Reported once; later samples are dropped.
+    Reached,
+}
 
-        // Wait for stop signal
-        let _ = rx.recv();
+/// Append `parts` to `buf` up to the recording's limits, spilling to disk if enabled.
+fn append(
+    progress: &mut Progress,
+    limits: &RecordingLimits,
+    format: (u32, u16),
+    buf: &mut Vec<f32>,
+    parts: [&[f32]; 2],
+) -> Limit {
+    if progress.full {
+        return Limit::Within;
+    }
 
-        // Stream is dropped here
-    });
+    let max = limits.max_samples(format);
+    // A warm capture's pre-roll may already be past a small limit.
+    let remaining = |buf: &Vec<f32>, progress: &Progress| {
+        max.map(|max| max.saturating_sub(buf.len() + progress.spilled()))
+    };
+    let mut room = remaining(buf, progress).unwrap_or(usize::MAX);
+    for part in parts {
+        let kept = &part[..part.len().min(room)];
+        buf.extend_from_slice(kept);
+        room -= kept.len();
+    }
+    if limits.spill_to_disk && !progress.spill_failed {
+        spill(progress, buf, format);
+    }
 
-    *is_recording = true;
-    Ok(selected_device_name)
+    let Some(remaining) = remaining(buf, progress) else {
+        return Limit::Within;
+    };
+    let remaining_secs = remaining / (format.0 as usize * format.1 as usize);
+    if remaining == 0 {
+        progress.full = true;
+        Limit::Reached
+    } else if !progress.warned && remaining_secs <= LIMIT_WARNING_SECS as usize {
+        progress.warned = true;
+        Limit::Near(remaining_secs as u32)
+    } else {
+        Limit::Within
+    }
 }
 
-fn write_input_data(input: &[f32], buffer: &Arc<Mutex<Vec<f32>>>, window: &WebviewWindow) {
-    if let Ok(mut buf) = buffer.lock() {
-        buf.extend_from_slice(input);
+/// Move the buffered samples to the recording file, creating it on first use.
+fn spill(progress: &mut Progress, buf: &mut Vec<f32>, format: (u32, u16)) {
+    if progress.spill.is_none() {
+        match hound::WavWriter::create(recording_path(), wav_spec(format)) {
+            Ok(writer) => progress.spill = Some(writer),
+            Err(e) => {
+                tracing::warn!(
+                    "failed to create recording file, keeping it in memory: {}",
+                    e
+                );
+                progress.spill_failed = true;
+                return;
+            }
+        }
+    }
+    let Some(writer) = progress.spill.as_mut() else {
+        return;
+    };
+    for (written, &sample) in buf.iter().enumerate() {
+        if let Err(e) = writer.write_sample(sample) {
+            tracing::warn!(
+                "failed to write recording file, keeping the rest in memory: {}",
+                e
+            );
+            progress.spill_failed = true;
+            buf.drain(..written);
+            return;
+        }
+    }
+    buf.clear();
+}
 
-        // Calculate RMS for visualization
-        let sum_squares: f32 = input.iter().map(|&x| x * x).sum();
-        let rms = (sum_squares / input.len() as f32).sqrt();
+/// Keep the stream and buffer but stop appending samples, so the recording resumes
+/// without reopening the device and the paused part is left out of the transcription.
+#[tauri::command]
+pub fn pause_recording(window: WebviewWindow, state: State<AudioState>) -> Result<(), AppError> {
+    set_paused(&window, &state, true)
+}
 
-        // Emit event (throttle this in production, but okay for now)
-        let _ = window.emit("audio-level-update", AudioLevelEvent { amplitude: rms });
+#[tauri::command]
+pub fn resume_recording(window: WebviewWindow, state: State<AudioState>) -> Result<(), AppError> {
+    set_paused(&window, &state, false)
+}
+
+fn set_paused(window: &WebviewWindow, state: &AudioState, paused: bool) -> Result<(), AppError> {
+    let is_recording = state.is_recording.lock()?;
+    if !*is_recording {
+        return Err(AppError::NotRecording);
+    }
+    if let Some(capture) = state.capture.lock()?.as_ref() {
+        capture.sink.paused.store(paused, Ordering::Release);
     }
+    drop(is_recording);
+
+    tracing::info!(paused, "recording pause toggled");
+    let _ = window.emit("recording-paused", RecordingPausedEvent { paused });
+    let _ = crate::tray::refresh_menu(window.app_handle());
+    Ok(())
 }
 
 #[tauri::command]
-pub fn stop_recording(state: State<AudioState>) -> Result<String, String> {
-    let mut is_recording = state.is_recording.lock().map_err(|e| e.to_string())?;
+pub fn stop_recording(window: WebviewWindow, state: State<AudioState>) -> Result<String, AppError> {
+    let mut is_recording = state.is_recording.lock()?;
     if !*is_recording {
-        return Err("Not recording".to_string());
+        return Err(AppError::NotRecording);
     }
 
-    // Send stop signal
-    if let Some(tx) = state.stop_tx.lock().map_err(|e| e.to_string())?.take() {
-        let _ = tx.send(());
+    *is_recording = false;
+
+    // Joining the capture thread guarantees the ring has been drained into the buffer.
+    // Taken out first so the capture lock isn't held while the thread is joined.
+    let capture = state.capture.lock()?.take();
+    let mut spill = None;
+    if let Some(capture) = capture {
+        let progress = capture.sink.progress.clone();
+        capture.stop()?;
+        spill = progress.lock()?.spill.take();
     }
 
-    *is_recording = false;
+    let path = match spill {
+        Some(writer) => finish_spill(writer, &state)?,
+        None => write_recording(&state)?,
+    };
 
-    // Wait a bit for thread to finish writing?
-    // The buffer is protected by Mutex, so we are fine.
-    // But the thread might still be writing the LAST chunk.
-    // Ideally we should wait for thread join, but we didn't store the handle.
-    // For now, a small sleep or just assuming it's fast enough is "okay" for Phase 1.
-    // Better: use a channel to ack the stop.
-    // But let's keep it simple.
-    std::thread::sleep(std::time::Duration::from_millis(100));
-
-    let buffer = state.recording_buffer.lock().map_err(|e| e.to_string())?;
-    let (sample_rate, channels) = state
-        .format
-        .lock()
-        .map_err(|e| e.to_string())?
-        .ok_or("No recording format found")?;
+    // Go back to keeping a pre-roll for the next recording.
+    if let Err(e) = rewarm(&window, &state) {
+        tracing::warn!("failed to reopen warm capture: {}", e);
+    }
+    drop(is_recording);
+    let _ = crate::tray::refresh_menu(window.app_handle());
 
-    // Save to WAV
-    let spec = hound::WavSpec {
+    Ok(path)
+}
+
+/// Where the last recording is written, and read back for transcription.
+pub fn recording_path() -> PathBuf {
+    std::env::temp_dir().join("recording_test.wav")
+}
+
+fn wav_spec((sample_rate, channels): (u32, u16)) -> hound::WavSpec {
+    hound::WavSpec {
         channels,
         sample_rate,
         bits_per_sample: 32,
         sample_format: hound::SampleFormat::Float,
-    };
+    }
+}
+
+fn write_recording(state: &AudioState) -> Result<String, AppError> {
+    let buffer = state.recording_buffer.lock()?;
+    let format = state.format.lock()?.ok_or(AppError::NoRecording)?;
 
-    let temp_dir = std::env::temp_dir();
-    let path = temp_dir.join("recording_test.wav");
-    let mut writer = hound::WavWriter::create(&path, spec).map_err(|e| e.to_string())?;
+    // Save to WAV
+    let path = recording_path();
+    let mut writer = hound::WavWriter::create(&path, wav_spec(format))?;
 
     for &sample in buffer.iter() {
-        writer.write_sample(sample).map_err(|e| e.to_string())?;
+        writer.write_sample(sample)?;
     }
-    writer.finalize().map_err(|e| e.to_string())?;
+    writer.finalize()?;
 
     Ok(path.to_string_lossy().to_string())
 }
 
+/// Finish a recording that was spilled to disk, including anything still in memory.
+fn finish_spill(
+    mut writer: hound::WavWriter<BufWriter<File>>,
+    state: &AudioState,
+) -> Result<String, AppError> {
+    let mut buffer = state.recording_buffer.lock()?;
+    for &sample in buffer.iter() {
+        writer.write_sample(sample)?;
+    }
+    buffer.clear();
+    writer.finalize()?;
+
+    Ok(recording_path().to_string_lossy().to_string())
+}
+
 #[tauri::command]
-pub fn play_recording() -> Result<(), String> {
-    let temp_dir = std::env::temp_dir();
-    let path = temp_dir.join("recording_test.wav");
+pub fn play_recording() -> Result<(), AppError> {
+    let path = recording_path();
 
     if !path.exists() {
-        return Err("No recording found".to_string());
+        return Err(AppError::NoRecording);
     }
 
     std::thread::spawn(move || {
@@ -240,3 +1641,251 @@ pub fn play_recording() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    /// Counts allocations per thread, so tests running in parallel don't show up in each
+    /// other's counts.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn count_allocation() {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count_allocation();
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            count_allocation();
+            unsafe { System.alloc_zeroed(layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count_allocation();
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Allocations `f` makes on this thread.
+    fn allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    /// Drives the data callback with `T` samples until the ring overflows.
+    fn assert_callback_does_not_allocate<T>(sample: T)
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let format = (48000, 2);
+        let overruns = Arc::new(AtomicUsize::new(0));
+        let (writer, consumer) = ring(format, &overruns);
+        let mut callback = data_callback::<T>(writer);
+        // One 10 ms buffer; 120 of them are more than the ring holds.
+        let data = vec![sample; 960];
+        let count = allocations(|| {
+            for _ in 0..120 {
+                callback(&data);
+            }
+        });
+        assert_eq!(count, 0);
+        assert_eq!(consumer.slots(), 96000);
+        assert_eq!(overruns.load(Ordering::Relaxed), 120 * 960 - 96000);
+    }
+
+    #[test]
+    fn data_callback_does_not_allocate() {
+        assert_callback_does_not_allocate(0.5f32);
+        assert_callback_does_not_allocate(i16::MAX / 2);
+        assert_callback_does_not_allocate(u16::MAX / 4);
+        assert_callback_does_not_allocate(cpal::I24::new(1 << 20).unwrap());
+        assert_callback_does_not_allocate(i32::MIN);
+    }
+
+    #[test]
+    fn ring_writer_converts_to_f32() {
+        let overruns = Arc::new(AtomicUsize::new(0));
+        let (mut writer, mut consumer) = ring((8000, 1), &overruns);
+        writer.write(&[i16::MIN, 0, 16384]);
+        let samples: Vec<f32> = std::iter::from_fn(|| consumer.pop().ok()).collect();
+        assert_eq!(samples, [-1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn ring_writer_keeps_whole_frames() {
+        let overruns = Arc::new(AtomicUsize::new(0));
+        let (mut writer, consumer) = ring((3, 2), &overruns);
+        // Room for three frames; the fourth and the odd sample are dropped.
+        writer.write(&[0.0f32; 9]);
+        assert_eq!(consumer.slots(), 6);
+        assert_eq!(overruns.load(Ordering::Relaxed), 3);
+    }
+
+    fn limits(max_duration_secs: u32) -> RecordingLimits {
+        RecordingLimits {
+            max_duration_secs: Some(max_duration_secs),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn preroll_past_the_limit_reaches_it() {
+        // Ten samples a second, so the limit is 10 samples and the pre-roll 25.
+        let format = (10, 1);
+        let mut progress = Progress::default();
+        let mut buf = vec![0.0; 25];
+        let limit = append(
+            &mut progress,
+            &limits(1),
+            format,
+            &mut buf,
+            [&[1.0; 5], &[]],
+        );
+        assert_eq!(limit, Limit::Reached);
+        assert_eq!(buf.len(), 25);
+        // Reported once, and nothing is appended afterwards.
+        let limit = append(
+            &mut progress,
+            &limits(1),
+            format,
+            &mut buf,
+            [&[1.0; 5], &[]],
+        );
+        assert_eq!(limit, Limit::Within);
+        assert_eq!(buf.len(), 25);
+    }
+
+    #[test]
+    fn samples_past_the_limit_are_dropped() {
+        let format = (10, 2);
+        let mut progress = Progress::default();
+        let mut buf = vec![0.0; 30];
+        let limit = append(
+            &mut progress,
+            &limits(2),
+            format,
+            &mut buf,
+            [&[1.0; 6], &[2.0; 6]],
+        );
+        assert_eq!(limit, Limit::Reached);
+        assert_eq!(buf.len(), 40);
+        assert_eq!(
+            &buf[30..],
+            [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 2.0]
+        );
+    }
+
+    #[test]
+    fn limit_warning_is_sent_once() {
+        let limits = limits(LIMIT_WARNING_SECS + 10);
+        let mut progress = Progress::default();
+        let mut buf = Vec::new();
+        // Five seconds at a time.
+        let mut next = || append(&mut progress, &limits, (10, 1), &mut buf, [&[0.0; 50], &[]]);
+        assert_eq!(next(), Limit::Within);
+        assert_eq!(next(), Limit::Near(LIMIT_WARNING_SECS));
+        assert_eq!(next(), Limit::Within);
+    }
+
+    #[test]
+    fn unlimited_recordings_keep_everything() {
+        let mut progress = Progress::default();
+        let mut buf = vec![0.0; 10];
+        let limits = RecordingLimits::default();
+        let limit = append(
+            &mut progress,
+            &limits,
+            (10, 1),
+            &mut buf,
+            [&[1.0; 5], &[2.0; 5]],
+        );
+        assert_eq!(limit, Limit::Within);
+        assert_eq!(buf.len(), 20);
+    }
+
+    #[test]
+    fn hosts_are_built_once() {
+        let hosts = Hosts::default();
+        let first = hosts.get(None).unwrap();
+        assert!(Arc::ptr_eq(&first, &hosts.get(None).unwrap()));
+        let named = hosts.get(Some(&first.id().to_string())).unwrap();
+        assert!(Arc::ptr_eq(&first, &named));
+    }
+
+    #[test]
+    fn device_ids_survive_a_second_enumeration() {
+        let hosts = Hosts::default();
+        for id in cpal::available_hosts() {
+            let Ok(host) = hosts.get(Some(&id.to_string())) else {
+                continue;
+            };
+            let first = list_input_devices(&host).unwrap_or_default();
+            let second = list_input_devices(&host).unwrap_or_default();
+            assert_eq!(first, second, "{} device IDs changed", id);
+        }
+    }
+
+    #[test]
+    fn jack_ids_name_the_direction() {
+        assert_eq!(jack_device_id(true), "jack:input");
+        assert_eq!(jack_device_id(false), "jack:output");
+    }
+
+    #[test]
+    fn ids_without_a_host_are_looked_up_on_the_given_host() {
+        let hosts = Hosts::default();
+        let host = hosts.get(None).unwrap();
+        let own = format!("{}:device", host.id());
+        for id in ["Microphone (USB Audio)", "hw:CARD=USB,DEV=0", own.as_str()] {
+            assert!(Arc::ptr_eq(&hosts.for_device(&host, id).unwrap(), &host));
+        }
+    }
+
+    #[test]
+    fn unknown_hosts_are_unavailable() {
+        assert!(Hosts::default().get(Some("nonexistent")).is_err());
+    }
+
+    #[test]
+    fn identical_devices_get_distinct_ids() {
+        let mut taken = Vec::new();
+        let ids: Vec<String> = [
+            "alsa:USB Mic",
+            "alsa:Built-in",
+            "alsa:USB Mic",
+            "alsa:USB Mic",
+        ]
+        .into_iter()
+        .map(|id| numbered(id.to_string(), &mut taken))
+        .collect();
+        assert_eq!(
+            ids,
+            [
+                "alsa:USB Mic",
+                "alsa:Built-in",
+                "alsa:USB Mic#2",
+                "alsa:USB Mic#3"
+            ]
+        );
+    }
+}
@@ -0,0 +1,541 @@
+use super::{CaptureSink, Hosts, Input, InputDevice, OpenInput, ring};
+use crate::audio_utils::ChannelSelection;
+use crate::error::AppError;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+
+/// How far system audio may run ahead of the microphone before the oldest samples are
+/// dropped. The two devices' clocks drift apart over a long recording.
+const MAX_BACKLOG_SECS: f32 = 0.2;
+
+/// Where system audio is recorded from.
+pub enum SystemDevice {
+    /// Loopback of an output device: an input stream opened on it records what it plays.
+    /// Supported by WASAPI and by CoreAudio on macOS 14.6 and later.
+    #[cfg(not(target_os = "linux"))]
+    Output(cpal::Device),
+    /// A PulseAudio or PipeWire monitor source, by name, or the default sink's monitor.
+    /// ALSA doesn't list monitors, so they are found with `pactl` and read with `parec`.
+    #[cfg(target_os = "linux")]
+    Monitor(Option<String>),
+}
+
+/// Output devices of the given host whose playback can be recorded.
+#[cfg(not(target_os = "linux"))]
+pub fn list_output_devices(host: &cpal::Host) -> Result<Vec<InputDevice>, AppError> {
+    use cpal::traits::HostTrait;
+    let devices = host.output_devices().map_err(AppError::audio)?;
+    Ok(super::name_devices(host.id(), devices))
+}
+
+/// Monitor sources of the sound server, whatever the host; IDs are the source names.
+#[cfg(target_os = "linux")]
+pub fn list_output_devices(_host: &cpal::Host) -> Result<Vec<InputDevice>, AppError> {
+    check_monitor()?;
+    let output = std::process::Command::new("pactl")
+        .args(["list", "sources"])
+        // The field labels are translated otherwise.
+        .env("LC_ALL", "C")
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .map_err(|e| missing_parec(&e))?;
+    if !output.status.success() {
+        return Err(AppError::Audio("pactl could not list sources".to_string()));
+    }
+    Ok(parse_monitors(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Monitor sources in the output of `pactl list sources`, named by their description.
+#[cfg(target_os = "linux")]
+fn parse_monitors(listing: &str) -> Vec<InputDevice> {
+    let mut sources: Vec<InputDevice> = Vec::new();
+    for line in listing.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("Name: ") {
+            sources.push(InputDevice {
+                id: name.to_string(),
+                name: name.to_string(),
+            });
+        } else if let Some(description) = line.strip_prefix("Description: ")
+            && let Some(source) = sources.last_mut()
+        {
+            source.name = description.to_string();
+        }
+    }
+    sources.retain(|s| s.id.ends_with(".monitor"));
+    sources
+}
+
+/// The output device with the given ID, or the default output when `id` is `None`.
+#[cfg(not(target_os = "linux"))]
+pub fn find(
+    hosts: &Hosts,
+    host: &Arc<cpal::Host>,
+    id: Option<&str>,
+) -> Result<SystemDevice, AppError> {
+    use cpal::traits::HostTrait;
+    let device = match id {
+        None => host
+            .default_output_device()
+            .ok_or(AppError::NoInputDevice)?,
+        Some(id) => {
+            let host = hosts.for_device(host, id)?;
+            let devices = host.output_devices().map_err(AppError::audio)?;
+            super::with_ids(host.id(), devices)
+                .into_iter()
+                .find(|(device_id, _)| device_id == id)
+                .map(|(_, device)| device)
+                .ok_or_else(|| AppError::DeviceNotFound(id.to_string()))?
+        }
+    };
+    Ok(SystemDevice::Output(device))
+}
+
+/// The monitor source with the given name, or the default sink's monitor when `id` is
+/// `None`.
+#[cfg(target_os = "linux")]
+pub fn find(
+    _hosts: &Hosts,
+    host: &Arc<cpal::Host>,
+    id: Option<&str>,
+) -> Result<SystemDevice, AppError> {
+    match id {
+        None => Ok(SystemDevice::Monitor(None)),
+        Some(id) => list_output_devices(host)?
+            .into_iter()
+            .find(|d| d.id == id)
+            .map(|d| SystemDevice::Monitor(Some(d.id)))
+            .ok_or_else(|| AppError::DeviceNotFound(id.to_string())),
+    }
+}
+
+/// Start recording system audio into a ring buffer.
+pub fn open(
+    device: &SystemDevice,
+    sink: &CaptureSink,
+    overruns: &Arc<AtomicUsize>,
+) -> Result<OpenInput, AppError> {
+    match device {
+        #[cfg(not(target_os = "linux"))]
+        SystemDevice::Output(device) => open_loopback(device, sink, overruns),
+        #[cfg(target_os = "linux")]
+        SystemDevice::Monitor(source) => open_monitor(source.as_deref(), sink, overruns),
+    }
+}
+
+/// The sample rate and channels `open` delivers.
+pub fn format(device: &SystemDevice) -> Result<(u32, u16), AppError> {
+    match device {
+        #[cfg(not(target_os = "linux"))]
+        SystemDevice::Output(device) => {
+            use cpal::traits::DeviceTrait;
+            let config = device.default_output_config().map_err(AppError::audio)?;
+            Ok((config.sample_rate(), config.channels()))
+        }
+        #[cfg(target_os = "linux")]
+        SystemDevice::Monitor(_) => Ok(MONITOR_FORMAT),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_loopback(
+    device: &cpal::Device,
+    sink: &CaptureSink,
+    overruns: &Arc<AtomicUsize>,
+) -> Result<OpenInput, AppError> {
+    use cpal::traits::{DeviceTrait, StreamTrait};
+    // Shared-mode loopback delivers the output's mix format, so there is nothing to pick.
+    let config = device.default_output_config().map_err(AppError::audio)?;
+    let format = (config.sample_rate(), config.channels());
+    let (writer, consumer) = ring(format, overruns);
+    let stream = super::build_stream(
+        device,
+        &config.config(),
+        config.sample_format(),
+        writer,
+        super::LossReport {
+            control_tx: sink.control_tx.clone(),
+            event: super::CaptureEvent::SystemLost("Output device disconnected".to_string()),
+        },
+    )?;
+    stream.play().map_err(AppError::audio)?;
+
+    let name = super::display_name(device);
+    tracing::info!(
+        device = %name,
+        sample_rate = format.0,
+        channels = format.1,
+        "system audio loopback opened"
+    );
+    Ok(OpenInput {
+        input: Input::Stream(stream),
+        consumer,
+        format,
+        name,
+    })
+}
+
+#[cfg(target_os = "linux")]
+const MONITOR_FORMAT: (u32, u16) = (48000, 2);
+/// Restarts of `parec` in a row before system audio is given up on.
+#[cfg(target_os = "linux")]
+const MONITOR_RETRIES: u32 = 5;
+/// Wait before the first restart, doubled for each one after.
+#[cfg(target_os = "linux")]
+const MONITOR_BACKOFF: std::time::Duration = std::time::Duration::from_millis(250);
+/// A `parec` that ran this long was working, so its exit starts a new count of restarts.
+#[cfg(target_os = "linux")]
+const MONITOR_HEALTHY: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// `parec`'s name for the default sink's monitor.
+#[cfg(target_os = "linux")]
+const DEFAULT_MONITOR: &str = "@DEFAULT_MONITOR@";
+
+#[cfg(target_os = "linux")]
+fn open_monitor(
+    source: Option<&str>,
+    sink: &CaptureSink,
+    overruns: &Arc<AtomicUsize>,
+) -> Result<OpenInput, AppError> {
+    check_monitor()?;
+    let source = source.unwrap_or(DEFAULT_MONITOR).to_string();
+    let (writer, consumer) = ring(MONITOR_FORMAT, overruns);
+    let reader = MonitorReader::spawn(source.clone(), writer, sink.control_tx.clone())?;
+    tracing::info!(%source, "system audio monitor opened");
+    Ok(OpenInput {
+        input: Input::Monitor(reader),
+        consumer,
+        format: MONITOR_FORMAT,
+        name: "System Audio".to_string(),
+    })
+}
+
+/// Fail before capturing when `parec` is missing or no sound server answers, as on a
+/// JACK-only system, rather than finding out from `parec` exiting straight away.
+#[cfg(target_os = "linux")]
+fn check_monitor() -> Result<(), AppError> {
+    use std::process::{Command, Stdio};
+
+    let run = |program: &str, arg: &str| {
+        Command::new(program)
+            .arg(arg)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    };
+    run("parec", "--version").map_err(|e| missing_parec(&e))?;
+    // pactl comes with parec; only a server that answered is reported missing.
+    match run("pactl", "info") {
+        Ok(status) if !status.success() => Err(AppError::Audio(
+            "Recording system audio needs a PulseAudio or PipeWire server".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The error for a `parec` or `pactl` that couldn't be run, usually because PipeWire is
+/// installed without pulseaudio-utils.
+#[cfg(target_os = "linux")]
+fn missing_parec(e: &std::io::Error) -> AppError {
+    AppError::Audio(format!(
+        "Recording system audio needs parec and pactl (pulseaudio-utils): {}",
+        e
+    ))
+}
+
+/// `parec` recording a monitor source as raw float samples, with a thread
+/// moving them into the ring. Works with PipeWire through pipewire-pulse. The thread
+/// restarts `parec` when it exits, backing off, and reports `SystemLost` once it gives up.
+#[cfg(target_os = "linux")]
+pub struct MonitorReader {
+    /// The running `parec`, replaced on restart; `None` once stopped.
+    child: Arc<std::sync::Mutex<Option<std::process::Child>>>,
+    /// Dropped to cut a wait between restarts short.
+    wake: Option<std::sync::mpsc::Sender<()>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+#[cfg(target_os = "linux")]
+impl MonitorReader {
+    fn spawn(
+        source: String,
+        mut writer: super::RingWriter,
+        control_tx: std::sync::mpsc::Sender<super::CaptureEvent>,
+    ) -> Result<Self, AppError> {
+        use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+        use std::time::Instant;
+
+        let (child, stdout) = spawn_parec(&source)?;
+        let child = Arc::new(std::sync::Mutex::new(Some(child)));
+        let (wake, woken) = std::sync::mpsc::channel::<()>();
+
+        let thread = {
+            let child = child.clone();
+            std::thread::spawn(move || {
+                let mut stdout = Some(stdout);
+                let mut failures = 0;
+                loop {
+                    let started = Instant::now();
+                    let error = match stdout.take() {
+                        Some(mut stdout) => {
+                            pump(&mut stdout, &mut writer);
+                            "parec exited".to_string()
+                        }
+                        None => "parec could not be started".to_string(),
+                    };
+                    // Stopped on purpose: the reader was dropped before killing parec.
+                    if woken.try_recv() == Err(TryRecvError::Disconnected) {
+                        return;
+                    }
+                    if started.elapsed() >= MONITOR_HEALTHY {
+                        failures = 0;
+                    }
+                    failures += 1;
+                    if failures > MONITOR_RETRIES {
+                        let reason = format!("{} {} times", error, failures);
+                        let _ = control_tx.send(super::CaptureEvent::SystemLost(reason));
+                        return;
+                    }
+                    let delay = MONITOR_BACKOFF * 2u32.pow(failures - 1);
+                    tracing::warn!(attempt = failures, ?delay, "{}, restarting", error);
+                    if woken.recv_timeout(delay) != Err(RecvTimeoutError::Timeout) {
+                        return;
+                    }
+                    let Ok(mut current) = child.lock() else {
+                        return;
+                    };
+                    // Checked under the lock, so a stopped reader never gets a new parec.
+                    if current.is_none() {
+                        return;
+                    }
+                    match spawn_parec(&source) {
+                        Ok((new, out)) => {
+                            if let Some(mut old) = current.replace(new) {
+                                let _ = old.kill();
+                                let _ = old.wait();
+                            }
+                            stdout = Some(out);
+                        }
+                        Err(e) => tracing::warn!("{}", e),
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            child,
+            wake: Some(wake),
+            thread: Some(thread),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn spawn_parec(source: &str) -> Result<(std::process::Child, std::process::ChildStdout), AppError> {
+    use std::process::{Command, Stdio};
+
+    let (sample_rate, channels) = MONITOR_FORMAT;
+    let mut child = Command::new("parec")
+        .args([
+            &format!("--device={}", source),
+            "--format=float32le",
+            &format!("--rate={}", sample_rate),
+            &format!("--channels={}", channels),
+            "--latency-msec=20",
+            "--client-name=OpenSW",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| missing_parec(&e))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| AppError::Audio("parec has no output".to_string()))?;
+    Ok((child, stdout))
+}
+
+/// Move samples from `parec` into the ring until it exits.
+#[cfg(target_os = "linux")]
+fn pump(stdout: &mut std::process::ChildStdout, writer: &mut super::RingWriter) {
+    use std::io::Read;
+
+    let frame = size_of::<f32>() * MONITOR_FORMAT.1 as usize;
+    let mut bytes = vec![0u8; frame * 1024];
+    let mut filled = 0;
+    let mut samples = Vec::new();
+    loop {
+        match stdout.read(&mut bytes[filled..]) {
+            Ok(0) | Err(_) => return,
+            Ok(n) => filled += n,
+        }
+        // Only whole frames; the rest waits for the next read.
+        let whole = filled / frame * frame;
+        samples.clear();
+        samples.extend(
+            bytes[..whole]
+                .chunks_exact(size_of::<f32>())
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        );
+        writer.write(&samples);
+        bytes.copy_within(whole..filled, 0);
+        filled -= whole;
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for MonitorReader {
+    /// Stop `parec` and wait for the thread, so the ring's producer is gone afterwards.
+    fn drop(&mut self) {
+        drop(self.wake.take());
+        let child = self.child.lock().ok().and_then(|mut child| child.take());
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Combines the microphone with system audio into two channels at the microphone's rate:
+/// the microphone mixed down, and the system audio mixed down and resampled. The
+/// microphone sets the pace; system audio that hasn't arrived yet is silence, since
+/// loopback delivers nothing while nothing plays.
+pub struct Mixer {
+    mic_channels: usize,
+    mic_selection: ChannelSelection,
+    mic_gain: f32,
+    system: rtrb::Consumer<f32>,
+    system_channels: usize,
+    system_gain: f32,
+    resampler: LinearResampler,
+    pending: VecDeque<f32>,
+    max_backlog: usize,
+}
+
+impl Mixer {
+    pub fn new(
+        mic_format: (u32, u16),
+        mic_selection: ChannelSelection,
+        mic_gain_db: f32,
+        system: rtrb::Consumer<f32>,
+        system_format: (u32, u16),
+        system_gain_db: f32,
+    ) -> Self {
+        Self {
+            mic_channels: mic_format.1.max(1) as usize,
+            mic_selection,
+            mic_gain: 10f32.powf(mic_gain_db / 20.0),
+            system,
+            system_channels: system_format.1.max(1) as usize,
+            system_gain: 10f32.powf(system_gain_db / 20.0),
+            resampler: LinearResampler::new(system_format.0, mic_format.0),
+            pending: VecDeque::new(),
+            max_backlog: (MAX_BACKLOG_SECS * mic_format.0 as f32) as usize,
+        }
+    }
+
+    /// Interleaved microphone frames in, interleaved `[microphone, system]` frames out.
+    pub fn mix(&mut self, mic: [&[f32]; 2]) -> Vec<f32> {
+        let available = self.system.slots() / self.system_channels * self.system_channels;
+        if let Ok(chunk) = self.system.read_chunk(available) {
+            let (first, second) = chunk.as_slices();
+            for frame in first
+                .chunks_exact(self.system_channels)
+                .chain(second.chunks_exact(self.system_channels))
+            {
+                let mono = frame.iter().sum::<f32>() / frame.len() as f32;
+                self.resampler.push(mono, &mut self.pending);
+            }
+            chunk.commit_all();
+        }
+
+        let frames = mic.iter().map(|part| part.len()).sum::<usize>() / self.mic_channels;
+        let mut out = Vec::with_capacity(frames * 2);
+        for frame in mic
+            .iter()
+            .flat_map(|part| part.chunks_exact(self.mic_channels))
+        {
+            out.push(self.mic_selection.downmix(frame) * self.mic_gain);
+            out.push(self.pending.pop_front().unwrap_or(0.0) * self.system_gain);
+        }
+
+        let excess = self.pending.len().saturating_sub(self.max_backlog);
+        self.pending.drain(..excess);
+        out
+    }
+}
+
+/// Linear interpolation between sample rates; plenty for speech from a loopback.
+struct LinearResampler {
+    /// Input samples per output sample.
+    step: f64,
+    /// Position of the next output sample after `last`, in input samples.
+    pos: f64,
+    last: f32,
+}
+
+impl LinearResampler {
+    fn new(from: u32, to: u32) -> Self {
+        Self {
+            step: from as f64 / to.max(1) as f64,
+            pos: 0.0,
+            last: 0.0,
+        }
+    }
+
+    fn push(&mut self, x: f32, out: &mut VecDeque<f32>) {
+        while self.pos < 1.0 {
+            out.push_back(self.last + (x - self.last) * self.pos as f32);
+            self.pos += self.step;
+        }
+        self.pos -= 1.0;
+        self.last = x;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn monitors_are_parsed_from_pactl() {
+        let listing = "Source #0
+\tState: SUSPENDED
+\tName: alsa_output.pci-0000_00_1f.3.analog-stereo.monitor
+\tDescription: Monitor of Built-in Audio Analog Stereo
+\tDriver: PipeWire
+\tProperties:
+\t\tdevice.description = \"Built-in Audio\"
+
+Source #1
+\tState: RUNNING
+\tName: alsa_input.pci-0000_00_1f.3.analog-stereo
+\tDescription: Built-in Audio Analog Stereo
+
+Source #2
+\tName: bluez_output.00_11_22_33_44_55.1.monitor
+";
+        let monitors = super::parse_monitors(listing);
+        let pairs: Vec<(&str, &str)> = monitors
+            .iter()
+            .map(|d| (d.id.as_str(), d.name.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                (
+                    "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor",
+                    "Monitor of Built-in Audio Analog Stereo"
+                ),
+                (
+                    "bluez_output.00_11_22_33_44_55.1.monitor",
+                    "bluez_output.00_11_22_33_44_55.1.monitor"
+                ),
+            ]
+        );
+    }
+}
@@ -1,16 +1,413 @@
 use anyhow::{Context, Result, anyhow};
 use audioadapter_buffers::owned::SequentialOwned;
+use nnnoiseless::DenoiseState;
 use rubato::{
     Async, FixedAsync, Resampler, SincInterpolationParameters, SincInterpolationType,
     WindowFunction,
 };
 
-pub fn read_and_resample(path: &str) -> Result<Vec<f32>> {
+/// Which input channels make up the mono signal that gets transcribed. Channel numbers
+/// are zero-based.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ChannelSelection {
+    /// Average of every channel.
+    #[default]
+    Average,
+    Single {
+        channel: usize,
+    },
+    /// Average of two channels, e.g. one stereo pair of a multichannel interface.
+    Pair {
+        left: usize,
+        right: usize,
+    },
+}
+
+impl ChannelSelection {
+    /// Whether every selected channel exists in a stream with `channels` channels.
+    pub fn fits(&self, channels: usize) -> bool {
+        match *self {
+            ChannelSelection::Average => true,
+            ChannelSelection::Single { channel } => channel < channels,
+            ChannelSelection::Pair { left, right } => left < channels && right < channels,
+        }
+    }
+
+    /// Mix one interleaved frame down to a single sample. Falls back to the average when
+    /// the frame lacks a selected channel.
+    pub fn downmix(&self, frame: &[f32]) -> f32 {
+        let fits = self.fits(frame.len());
+        match *self {
+            ChannelSelection::Single { channel } if fits => frame[channel],
+            ChannelSelection::Pair { left, right } if fits => (frame[left] + frame[right]) / 2.0,
+            _ => frame.iter().sum::<f32>() / frame.len() as f32,
+        }
+    }
+}
+
+/// Level adjustments applied to the mixed-down signal. Gain and AGC run both in the live
+/// meter and before transcription; normalization needs the whole recording, so it only
+/// applies before transcription.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct GainSettings {
+    #[serde(default)]
+    pub gain_db: f32,
+    #[serde(default)]
+    pub normalize: Normalization,
+    /// Automatic gain control, for speakers moving towards and away from the mic.
+    #[serde(default)]
+    pub agc: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    #[default]
+    Off,
+    /// Scale the loudest sample to `PEAK_CEILING_DBFS`.
+    Peak,
+    /// Scale to `LOUDNESS_TARGET_LUFS` integrated loudness, as in EBU R128, without
+    /// peaks exceeding `PEAK_CEILING_DBFS`.
+    Loudness,
+}
+
+/// Highest peak after normalization.
+const PEAK_CEILING_DBFS: f32 = -1.0;
+const LOUDNESS_TARGET_LUFS: f32 = -23.0;
+/// Level AGC steers towards, as short-term RMS.
+const AGC_TARGET_DBFS: f32 = -20.0;
+const AGC_MAX_GAIN_DB: f32 = 24.0;
+const AGC_MIN_GAIN_DB: f32 = -12.0;
+/// Below this AGC holds its gain, so pauses don't pull the background noise up.
+const AGC_GATE_DBFS: f32 = -50.0;
+/// Time constants in seconds: level detection, gain reduction and gain recovery.
+const AGC_WINDOW_SECS: f32 = 0.1;
+const AGC_ATTACK_SECS: f32 = 0.05;
+const AGC_RELEASE_SECS: f32 = 1.5;
+
+fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// One-pole smoothing coefficient for time constant `secs`.
+fn smoothing(secs: f32, sample_rate: f32) -> f32 {
+    1.0 - (-1.0 / (secs * sample_rate)).exp()
+}
+
+/// Manual gain followed by AGC, sample by sample.
+pub struct Gain {
+    gain: f32,
+    agc: Option<Agc>,
+}
+
+struct Agc {
+    power: f32,
+    gain: f32,
+    window: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl Gain {
+    pub fn new(settings: &GainSettings, sample_rate: u32) -> Self {
+        let rate = sample_rate as f32;
+        Self {
+            gain: db_to_linear(settings.gain_db),
+            agc: settings.agc.then(|| Agc {
+                power: 0.0,
+                gain: 1.0,
+                window: smoothing(AGC_WINDOW_SECS, rate),
+                attack: smoothing(AGC_ATTACK_SECS, rate),
+                release: smoothing(AGC_RELEASE_SECS, rate),
+            }),
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let mut y = x * self.gain;
+        if let Some(agc) = &mut self.agc {
+            agc.power += agc.window * (y * y - agc.power);
+            let level = agc.power.sqrt();
+            if level > db_to_linear(AGC_GATE_DBFS) {
+                let target = (db_to_linear(AGC_TARGET_DBFS) / level)
+                    .clamp(db_to_linear(AGC_MIN_GAIN_DB), db_to_linear(AGC_MAX_GAIN_DB));
+                let rate = if target < agc.gain {
+                    agc.attack
+                } else {
+                    agc.release
+                };
+                agc.gain += rate * (target - agc.gain);
+            }
+            y *= agc.gain;
+        }
+        y.clamp(-1.0, 1.0)
+    }
+}
+
+/// Apply `normalization` to a whole mono recording in place.
+pub fn normalize(samples: &mut [f32], sample_rate: u32, normalization: Normalization) {
+    let peak = samples.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+    if peak == 0.0 {
+        return;
+    }
+    let peak_gain = db_to_linear(PEAK_CEILING_DBFS) / peak;
+    let gain = match normalization {
+        Normalization::Off => return,
+        Normalization::Peak => peak_gain,
+        Normalization::Loudness => match integrated_loudness(samples, sample_rate) {
+            Some(lufs) => db_to_linear(LOUDNESS_TARGET_LUFS - lufs).min(peak_gain),
+            // Nothing above the absolute gate: silence, leave it alone.
+            None => return,
+        },
+    };
+    samples.iter_mut().for_each(|x| *x *= gain);
+}
+
+/// Integrated loudness in LUFS per ITU-R BS.1770 (K-weighting, 400 ms blocks with 75%
+/// overlap, absolute gate at -70 LUFS and relative gate 10 LU below), or `None` if every
+/// block is below the absolute gate.
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let rate = sample_rate as f32;
+    // BS.1770 pre-filter and RLB high-pass, redesigned for this sample rate. At 48 kHz
+    // these give the coefficients from the recommendation.
+    let mut shelf = Biquad::k_shelf(rate);
+    let mut high_pass = Biquad::k_high_pass(rate);
+    let weighted: Vec<f32> = samples
+        .iter()
+        .map(|&x| high_pass.process(shelf.process(x)))
+        .collect();
+
+    let block = (0.4 * rate) as usize;
+    let step = block / 4;
+    if block == 0 || weighted.len() < block {
+        return None;
+    }
+    let powers: Vec<f32> = (0..=(weighted.len() - block) / step)
+        .map(|i| {
+            let block = &weighted[i * step..i * step + block];
+            block.iter().map(|x| x * x).sum::<f32>() / block.len() as f32
+        })
+        .collect();
+
+    let loudness = |power: f32| -0.691 + 10.0 * power.log10();
+    let gated_mean = |threshold: f32| {
+        let gated: Vec<f32> = powers
+            .iter()
+            .copied()
+            .filter(|&power| loudness(power) > threshold)
+            .collect();
+        (!gated.is_empty()).then(|| gated.iter().sum::<f32>() / gated.len() as f32)
+    };
+    let absolute = gated_mean(-70.0)?;
+    gated_mean(loudness(absolute) - 10.0).map(loudness)
+}
+
+/// Cleanup applied before transcription, for keyboard, HVAC and other office noise
+/// that makes whisper hallucinate. Each stage is off when unset.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct DspSettings {
+    /// Cutoff of the high-pass filter in Hz, against rumble and handling noise.
+    pub high_pass_hz: Option<f32>,
+    /// Level in dBFS below which the noise gate closes.
+    pub gate_threshold_dbfs: Option<f32>,
+    /// RNNoise denoiser.
+    #[serde(default)]
+    pub denoise: bool,
+}
+
+/// The only rate RNNoise works at; other recordings are resampled for it.
+const DENOISE_SAMPLE_RATE: u32 = 48000;
+/// RNNoise expects samples in 16-bit range.
+const DENOISE_SCALE: f32 = 32768.0;
+/// The gate opens again this far above its threshold, so it doesn't chatter.
+const GATE_HYSTERESIS_DB: f32 = 6.0;
+/// Attenuation while closed; not silence, which whisper also hallucinates on.
+const GATE_RANGE_DB: f32 = -40.0;
+/// Time constants in seconds: opening, closing, and how long the gate stays open after
+/// the level drops.
+const GATE_ATTACK_SECS: f32 = 0.005;
+const GATE_RELEASE_SECS: f32 = 0.08;
+const GATE_HOLD_SECS: f32 = 0.15;
+
+/// Run the enabled stages on a mono recording: high-pass, then the denoiser, then the
+/// gate, which works best on what the denoiser leaves. `sample_rate` must be
+/// `DENOISE_SAMPLE_RATE` when denoising.
+pub fn apply_dsp(samples: &mut [f32], sample_rate: u32, settings: &DspSettings) {
+    let rate = sample_rate as f32;
+    if let Some(cutoff) = settings.high_pass_hz {
+        let mut filter = Biquad::high_pass(cutoff, std::f32::consts::FRAC_1_SQRT_2, rate);
+        samples.iter_mut().for_each(|x| *x = filter.process(*x));
+    }
+    if settings.denoise {
+        denoise(samples);
+    }
+    if let Some(threshold) = settings.gate_threshold_dbfs {
+        let mut gate = NoiseGate::new(threshold, rate);
+        samples.iter_mut().for_each(|x| *x = gate.process(*x));
+    }
+}
+
+/// RNNoise over 48 kHz mono, frame by frame. The last frame is padded with silence.
+fn denoise(samples: &mut [f32]) {
+    let mut state = DenoiseState::new();
+    let mut input = [0.0; DenoiseState::FRAME_SIZE];
+    let mut output = [0.0; DenoiseState::FRAME_SIZE];
+    for chunk in samples.chunks_mut(DenoiseState::FRAME_SIZE) {
+        input.fill(0.0);
+        for (dst, &x) in input.iter_mut().zip(chunk.iter()) {
+            *dst = x * DENOISE_SCALE;
+        }
+        state.process_frame(&mut output, &input);
+        for (x, &y) in chunk.iter_mut().zip(output.iter()) {
+            *x = y / DENOISE_SCALE;
+        }
+    }
+}
+
+/// Downward expander with hysteresis and hold, driven by a peak envelope.
+struct NoiseGate {
+    open_level: f32,
+    close_level: f32,
+    closed_gain: f32,
+    envelope: f32,
+    envelope_release: f32,
+    gain: f32,
+    attack: f32,
+    release: f32,
+    hold: usize,
+    hold_left: usize,
+    open: bool,
+}
+
+impl NoiseGate {
+    fn new(threshold_dbfs: f32, sample_rate: f32) -> Self {
+        Self {
+            open_level: db_to_linear(threshold_dbfs + GATE_HYSTERESIS_DB),
+            close_level: db_to_linear(threshold_dbfs),
+            closed_gain: db_to_linear(GATE_RANGE_DB),
+            envelope: 0.0,
+            envelope_release: smoothing(GATE_RELEASE_SECS, sample_rate),
+            gain: db_to_linear(GATE_RANGE_DB),
+            attack: smoothing(GATE_ATTACK_SECS, sample_rate),
+            release: smoothing(GATE_RELEASE_SECS, sample_rate),
+            hold: (GATE_HOLD_SECS * sample_rate) as usize,
+            hold_left: 0,
+            open: false,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let level = x.abs();
+        self.envelope = if level > self.envelope {
+            level
+        } else {
+            self.envelope + self.envelope_release * (level - self.envelope)
+        };
+
+        if self.envelope >= self.open_level {
+            self.open = true;
+            self.hold_left = self.hold;
+        } else if self.open && self.envelope < self.close_level {
+            if self.hold_left == 0 {
+                self.open = false;
+            } else {
+                self.hold_left -= 1;
+            }
+        }
+
+        let (target, rate) = if self.open {
+            (1.0, self.attack)
+        } else {
+            (self.closed_gain, self.release)
+        };
+        self.gain += rate * (target - self.gain);
+        x * self.gain
+    }
+}
+
+/// Biquad in transposed direct form II.
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    z: [f32; 2],
+}
+
+impl Biquad {
+    /// RBJ high-pass.
+    fn high_pass(freq: f32, q: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * std::f32::consts::PI * freq / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        Self::normalized(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// The K-weighting high shelf, about +4 dB above 1.7 kHz.
+    fn k_shelf(sample_rate: f32) -> Self {
+        let (f0, q, gain_db) = (1_681.974_5, 0.707_175_2, 3.999_843_8);
+        let k = (std::f32::consts::PI * f0 / sample_rate).tan();
+        let vh = 10f32.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_8);
+        Self::normalized(
+            [
+                vh + vb * k / q + k * k,
+                2.0 * (k * k - vh),
+                vh - vb * k / q + k * k,
+            ],
+            [
+                1.0 + k / q + k * k,
+                2.0 * (k * k - 1.0),
+                1.0 - k / q + k * k,
+            ],
+        )
+    }
+
+    /// The K-weighting high-pass at 38 Hz.
+    fn k_high_pass(sample_rate: f32) -> Self {
+        let (f0, q) = (38.135_47, 0.500_327);
+        let k = (std::f32::consts::PI * f0 / sample_rate).tan();
+        Self {
+            b: [1.0, -2.0, 1.0],
+            ..Self::normalized(
+                [0.0; 3],
+                [
+                    1.0 + k / q + k * k,
+                    2.0 * (k * k - 1.0),
+                    1.0 - k / q + k * k,
+                ],
+            )
+        }
+    }
+
+    fn normalized(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self {
+            b: b.map(|b| b / a[0]),
+            a: [a[1] / a[0], a[2] / a[0]],
+            z: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+pub fn read_and_resample(
+    path: &str,
+    selection: &ChannelSelection,
+    gain: &GainSettings,
+    dsp: &DspSettings,
+) -> Result<Vec<f32>> {
     let mut reader = hound::WavReader::open(path).context("Failed to open WAV file")?;
     let spec = reader.spec();
 
     let channels = spec.channels as usize;
-    let source_sample_rate = spec.sample_rate as usize;
     let target_sample_rate = 16000;
 
     // Read all samples
@@ -33,16 +430,35 @@ pub fn read_and_resample(path: &str) -> Result<Vec<f32>> {
     };
 
     // Convert to Mono
-    let mut mono_samples = Vec::with_capacity(samples.len() / channels);
-    if channels == 1 {
-        mono_samples = samples;
+    let mut mono_samples: Vec<f32> = if channels == 1 {
+        samples
     } else {
-        for chunk in samples.chunks(channels) {
-            let sum: f32 = chunk.iter().sum();
-            mono_samples.push(sum / channels as f32);
-        }
+        samples
+            .chunks(channels)
+            .map(|frame| selection.downmix(frame))
+            .collect()
+    };
+
+    let mut sample_rate = spec.sample_rate;
+    if dsp.denoise && sample_rate != DENOISE_SAMPLE_RATE {
+        mono_samples = resample(mono_samples, sample_rate, DENOISE_SAMPLE_RATE)?;
+        sample_rate = DENOISE_SAMPLE_RATE;
     }
+    apply_dsp(&mut mono_samples, sample_rate, dsp);
+
+    // Same gain stage as the live meter; its time constants don't depend on the rate.
+    let mut stage = Gain::new(gain, sample_rate);
+    mono_samples.iter_mut().for_each(|x| *x = stage.process(*x));
+    normalize(&mut mono_samples, sample_rate, gain.normalize);
+
+    resample(mono_samples, sample_rate, target_sample_rate)
+}
 
+fn resample(
+    mono_samples: Vec<f32>,
+    source_sample_rate: u32,
+    target_sample_rate: u32,
+) -> Result<Vec<f32>> {
     if source_sample_rate == target_sample_rate {
         return Ok(mono_samples);
     }
@@ -89,3 +505,252 @@ pub fn read_and_resample(path: &str) -> Result<Vec<f32>> {
     let resampled = output_data[..frames_written].to_vec();
     Ok(resampled)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mono 16-bit fixture from `tests/fixtures`, with its sample rate.
+    fn fixture(name: &str) -> (Vec<f32>, u32) {
+        let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+        let mut reader = hound::WavReader::open(path).unwrap();
+        let sample_rate = reader.spec().sample_rate;
+        let samples = reader
+            .samples::<i16>()
+            .map(|x| x.unwrap() as f32 / 32768.0)
+            .collect();
+        (samples, sample_rate)
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    /// Amplitude of the `freq` component over a whole number of its periods.
+    fn amplitude(samples: &[f32], freq: f32, sample_rate: u32) -> f32 {
+        let w = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+        let (re, im) = samples
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (n, &x)| {
+                let phase = w * n as f64;
+                (re + x as f64 * phase.cos(), im + x as f64 * phase.sin())
+            });
+        (2.0 * (re * re + im * im).sqrt() / samples.len() as f64) as f32
+    }
+
+    /// Samples from `from` to `to` seconds.
+    fn span(samples: &[f32], sample_rate: u32, from: f32, to: f32) -> &[f32] {
+        let at = |secs: f32| (secs * sample_rate as f32) as usize;
+        &samples[at(from)..at(to)]
+    }
+
+    #[test]
+    fn high_pass_removes_hum_and_keeps_speech_band() {
+        // 50 Hz hum and a 1 kHz tone, both at amplitude 0.25.
+        let (mut samples, rate) = fixture("hum_and_tone.wav");
+        let settings = DspSettings {
+            high_pass_hz: Some(120.0),
+            ..Default::default()
+        };
+        apply_dsp(&mut samples, rate, &settings);
+        // Past the filter's start-up, over whole periods of both tones.
+        let settled = span(&samples, rate, 0.1, 0.5);
+        assert!(amplitude(settled, 50.0, rate) < 0.25 * 0.2);
+        assert!(amplitude(settled, 1000.0, rate) > 0.25 * 0.97);
+    }
+
+    #[test]
+    fn gate_closes_on_noise_and_opens_for_signal() {
+        // -60 dBFS noise throughout, with a 1 kHz burst from 0.3 to 0.6 s.
+        let (input, rate) = fixture("noise_with_burst.wav");
+        let mut output = input.clone();
+        let settings = DspSettings {
+            gate_threshold_dbfs: Some(-40.0),
+            ..Default::default()
+        };
+        apply_dsp(&mut output, rate, &settings);
+        let ratio =
+            |from, to| rms(span(&output, rate, from, to)) / rms(span(&input, rate, from, to));
+        // Closed from the start, by the gate's full range.
+        assert!(ratio(0.0, 0.25) < 0.02);
+        assert!(ratio(0.35, 0.55) > 0.99);
+        // Closed again once the hold and release have passed.
+        assert!(ratio(1.4, 1.5) < 0.1);
+    }
+
+    #[test]
+    fn gate_holds_open_through_short_pauses() {
+        let (input, rate) = fixture("noise_with_burst.wav");
+        let mut output = input.clone();
+        let settings = DspSettings {
+            gate_threshold_dbfs: Some(-40.0),
+            ..Default::default()
+        };
+        apply_dsp(&mut output, rate, &settings);
+        // Right after the burst the envelope is still above the threshold.
+        let tail = span(&output, rate, 0.6, 0.65);
+        let original = span(&input, rate, 0.6, 0.65);
+        assert!(rms(tail) > rms(original) * 0.9);
+    }
+
+    #[test]
+    fn denoise_suppresses_stationary_noise() {
+        // Broadband noise at about -35 dBFS, already at RNNoise's rate.
+        let (input, rate) = fixture("white_noise.wav");
+        assert_eq!(rate, DENOISE_SAMPLE_RATE);
+        let mut output = input.clone();
+        let settings = DspSettings {
+            denoise: true,
+            ..Default::default()
+        };
+        apply_dsp(&mut output, rate, &settings);
+        assert_eq!(output.len(), input.len());
+        assert!(output.iter().all(|x| x.is_finite()));
+        // Once its noise estimate has settled, by at least 6 dB.
+        let settled = |samples| rms(span(samples, rate, 0.25, 0.5));
+        assert!(settled(&output) < settled(&input) * 0.5);
+    }
+
+    #[test]
+    fn disabled_stages_leave_samples_alone() {
+        let (input, rate) = fixture("hum_and_tone.wav");
+        let mut output = input.clone();
+        apply_dsp(&mut output, rate, &DspSettings::default());
+        assert_eq!(output, input);
+    }
+
+    /// `secs` of a sine at `freq` with peak `amplitude`.
+    fn sine(freq: f32, amplitude: f32, secs: f32, sample_rate: u32) -> Vec<f32> {
+        let w = 2.0 * std::f32::consts::PI * freq / sample_rate as f32;
+        (0..(secs * sample_rate as f32) as usize)
+            .map(|n| amplitude * (w * n as f32).sin())
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |peak, x| peak.max(x.abs()))
+    }
+
+    fn dbfs(level: f32) -> f32 {
+        20.0 * level.log10()
+    }
+
+    #[test]
+    fn reference_sine_measures_minus_23_lufs() {
+        // BS.1770: a 1 kHz sine peaking at -20 dBFS is -23 LUFS, at any sample rate.
+        for rate in [16000, 44100, 48000] {
+            let lufs = integrated_loudness(&sine(1000.0, 0.1, 3.0, rate), rate).unwrap();
+            assert!((lufs + 23.0).abs() < 0.5, "{} Hz: {} LUFS", rate, lufs);
+        }
+    }
+
+    #[test]
+    fn loudness_ignores_silence_around_speech() {
+        let rate = 16000;
+        let mut samples = vec![0.0; 2 * rate as usize];
+        samples.extend(sine(1000.0, 0.1, 3.0, rate));
+        samples.extend(vec![0.0; 2 * rate as usize]);
+        let lufs = integrated_loudness(&samples, rate).unwrap();
+        assert!((lufs + 23.0).abs() < 0.5, "{} LUFS", lufs);
+    }
+
+    #[test]
+    fn silence_and_short_input_have_no_loudness() {
+        assert_eq!(integrated_loudness(&vec![0.0; 48000], 48000), None);
+        // Below the -70 LUFS absolute gate.
+        assert_eq!(
+            integrated_loudness(&sine(1000.0, 1e-5, 1.0, 48000), 48000),
+            None
+        );
+        // Shorter than one 400 ms block.
+        assert_eq!(
+            integrated_loudness(&sine(1000.0, 0.1, 0.3, 48000), 48000),
+            None
+        );
+        assert_eq!(integrated_loudness(&[], 48000), None);
+    }
+
+    #[test]
+    fn peak_normalization_stops_at_the_ceiling() {
+        let ceiling = db_to_linear(PEAK_CEILING_DBFS);
+        for amplitude in [0.01, 0.5, 1.0] {
+            let mut samples = sine(440.0, amplitude, 0.5, 16000);
+            samples[100] = -amplitude * 1.5;
+            normalize(&mut samples, 16000, Normalization::Peak);
+            assert!((peak(&samples) - ceiling).abs() < 1e-4);
+        }
+
+        let mut silence = vec![0.0; 1600];
+        normalize(&mut silence, 16000, Normalization::Peak);
+        assert!(silence.iter().all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn loudness_normalization_reaches_the_target() {
+        let rate = 16000;
+        for amplitude in [0.01, 0.3] {
+            let mut samples = sine(1000.0, amplitude, 3.0, rate);
+            normalize(&mut samples, rate, Normalization::Loudness);
+            let lufs = integrated_loudness(&samples, rate).unwrap();
+            assert!((lufs - LOUDNESS_TARGET_LUFS).abs() < 0.5, "{} LUFS", lufs);
+        }
+    }
+
+    #[test]
+    fn loudness_normalization_respects_the_peak_ceiling() {
+        // A quiet recording with one click: reaching the target would clip the click.
+        let rate = 16000;
+        let mut samples = sine(1000.0, 0.01, 3.0, rate);
+        samples[rate as usize] = 0.5;
+        normalize(&mut samples, rate, Normalization::Loudness);
+        assert!(peak(&samples) <= db_to_linear(PEAK_CEILING_DBFS) + 1e-4);
+
+        let mut silence = vec![0.0; 3 * rate as usize];
+        normalize(&mut silence, rate, Normalization::Loudness);
+        assert!(silence.iter().all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn manual_gain_is_applied_and_clamped() {
+        let settings = GainSettings {
+            gain_db: 6.0,
+            ..Default::default()
+        };
+        let mut gain = Gain::new(&settings, 16000);
+        assert!((gain.process(0.25) - 0.25 * db_to_linear(6.0)).abs() < 1e-6);
+        assert_eq!(gain.process(0.9), 1.0);
+        assert_eq!(gain.process(-0.9), -1.0);
+    }
+
+    #[test]
+    fn agc_converges_after_a_step_without_pumping_or_clipping() {
+        let rate = 16000;
+        let settings = GainSettings {
+            agc: true,
+            ..Default::default()
+        };
+        let mut gain = Gain::new(&settings, rate);
+        // Loud, then 20 dB quieter, long enough for the slow recovery to settle.
+        let mut input = sine(1000.0, 0.5, 4.0, rate);
+        input.extend(sine(1000.0, 0.05, 8.0, rate));
+        let output: Vec<f32> = input.iter().map(|&x| gain.process(x)).collect();
+
+        for settled in [
+            span(&output, rate, 3.0, 4.0),
+            span(&output, rate, 11.0, 12.0),
+        ] {
+            // Close to the target level.
+            assert!((dbfs(rms(settled)) - AGC_TARGET_DBFS).abs() < 1.0);
+            // Steady from one 100 ms window to the next.
+            let levels: Vec<f32> = settled.chunks(1600).map(|c| dbfs(rms(c))).collect();
+            let (low, high) = levels.iter().fold((f32::MAX, f32::MIN), |(low, high), &l| {
+                (low.min(l), high.max(l))
+            });
+            assert!(high - low < 0.2, "level varies by {} dB", high - low);
+            assert!(peak(settled) < 0.5);
+        }
+        // Recovering towards a quieter input never overshoots into clipping.
+        assert!(peak(span(&output, rate, 4.0, 12.0)) < 0.5);
+    }
+}
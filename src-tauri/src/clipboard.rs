@@ -1,8 +1,13 @@
+use crate::error::AppError;
 use arboard::Clipboard;
-use std::error::Error;
 
-pub fn copy_text(text: &str) -> Result<(), Box<dyn Error>> {
+pub fn copy_text(text: &str) -> Result<(), AppError> {
     let mut clipboard = Clipboard::new()?;
     clipboard.set_text(text)?;
     Ok(())
 }
+
+pub fn read_text() -> Result<String, AppError> {
+    let mut clipboard = Clipboard::new()?;
+    Ok(clipboard.get_text()?)
+}
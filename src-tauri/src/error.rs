@@ -0,0 +1,209 @@
+use crate::ollama::OllamaError;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::sync::PoisonError;
+
+/// Error returned by every command. It reaches the frontend as
+/// `{ code, message, context }`: `code` picks a recovery action, `message` is shown to
+/// the user, and `context` carries the device, path or underlying error it concerns.
+#[derive(Debug)]
+pub enum AppError {
+    NoModelSelected,
+    ModelNotFound(String),
+    InvalidModel(String),
+    /// The user closed a file dialog without choosing anything.
+    NoFileSelected,
+    NoInputDevice,
+    DeviceNotFound(String),
+    AlreadyRecording,
+    NotRecording,
+    NoRecording,
+    Audio(String),
+    Transcription(String),
+    OllamaUnreachable(String),
+    RefineModelNotFound(String),
+    Refinement(String),
+    TemplateNotFound(String),
+    InvalidTemplateFile(String),
+    Shortcut(String),
+    Clipboard(String),
+    InvalidLogLevel(String),
+    Window(String),
+    Io(String),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NoModelSelected => "no_model_selected",
+            AppError::ModelNotFound(_) => "model_not_found",
+            AppError::InvalidModel(_) => "invalid_model",
+            AppError::NoFileSelected => "no_file_selected",
+            AppError::NoInputDevice => "no_input_device",
+            AppError::DeviceNotFound(_) => "device_not_found",
+            AppError::AlreadyRecording => "already_recording",
+            AppError::NotRecording => "not_recording",
+            AppError::NoRecording => "no_recording",
+            AppError::Audio(_) => "audio",
+            AppError::Transcription(_) => "transcription",
+            AppError::OllamaUnreachable(_) => "ollama_unreachable",
+            AppError::RefineModelNotFound(_) => "refine_model_not_found",
+            AppError::Refinement(_) => "refinement",
+            AppError::TemplateNotFound(_) => "template_not_found",
+            AppError::InvalidTemplateFile(_) => "invalid_template_file",
+            AppError::Shortcut(_) => "shortcut",
+            AppError::Clipboard(_) => "clipboard",
+            AppError::InvalidLogLevel(_) => "invalid_log_level",
+            AppError::Window(_) => "window",
+            AppError::Io(_) => "io",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        match self {
+            AppError::NoModelSelected
+            | AppError::NoFileSelected
+            | AppError::NoInputDevice
+            | AppError::AlreadyRecording
+            | AppError::NotRecording
+            | AppError::NoRecording => None,
+            AppError::ModelNotFound(s)
+            | AppError::InvalidModel(s)
+            | AppError::DeviceNotFound(s)
+            | AppError::Audio(s)
+            | AppError::Transcription(s)
+            | AppError::OllamaUnreachable(s)
+            | AppError::RefineModelNotFound(s)
+            | AppError::Refinement(s)
+            | AppError::TemplateNotFound(s)
+            | AppError::InvalidTemplateFile(s)
+            | AppError::Shortcut(s)
+            | AppError::Clipboard(s)
+            | AppError::InvalidLogLevel(s)
+            | AppError::Window(s)
+            | AppError::Io(s)
+            | AppError::Internal(s) => Some(s),
+        }
+    }
+
+    pub fn audio(e: impl fmt::Display) -> Self {
+        AppError::Audio(e.to_string())
+    }
+
+    /// For `anyhow` errors, whose alternate format includes the cause chain.
+    pub fn transcription(e: impl fmt::Display) -> Self {
+        AppError::Transcription(format!("{:#}", e))
+    }
+
+    pub fn internal(e: impl fmt::Display) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::NoModelSelected => write!(f, "No model selected"),
+            AppError::ModelNotFound(path) => write!(f, "Model file not found: {}", path),
+            AppError::InvalidModel(e) => write!(f, "Invalid model: {}", e),
+            AppError::NoFileSelected => write!(f, "No file selected"),
+            AppError::NoInputDevice => write!(f, "No input device available"),
+            AppError::DeviceNotFound(name) => write!(f, "Device '{}' not found", name),
+            AppError::AlreadyRecording => write!(f, "Already recording"),
+            AppError::NotRecording => write!(f, "Not recording"),
+            AppError::NoRecording => write!(f, "No recording found"),
+            AppError::Audio(e) => write!(f, "Audio error: {}", e),
+            AppError::Transcription(e) => write!(f, "Transcription failed: {}", e),
+            AppError::OllamaUnreachable(e) => write!(f, "{}", e),
+            AppError::RefineModelNotFound(e) => write!(f, "Refinement model not found: {}", e),
+            AppError::Refinement(e) => write!(f, "Refinement failed: {}", e),
+            AppError::TemplateNotFound(id) if id.is_empty() => {
+                write!(f, "No refinement template selected")
+            }
+            AppError::TemplateNotFound(id) => write!(f, "Template '{}' not found", id),
+            AppError::InvalidTemplateFile(e) => write!(f, "Invalid template file: {}", e),
+            AppError::Shortcut(e) => write!(f, "Failed to register shortcuts: {}", e),
+            AppError::Clipboard(e) => write!(f, "Clipboard error: {}", e),
+            AppError::InvalidLogLevel(e) => write!(f, "Invalid log level: {}", e),
+            AppError::Window(e) => write!(f, "Window error: {}", e),
+            AppError::Io(e) => write!(f, "File error: {}", e),
+            AppError::Internal(e) => write!(f, "Internal error: {}", e),
+        }
+    }
+}
+
+impl Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("AppError", 3)?;
+        s.serialize_field("code", self.code())?;
+        s.serialize_field("message", &self.to_string())?;
+        s.serialize_field("context", &self.context())?;
+        s.end()
+    }
+}
+
+impl From<OllamaError> for AppError {
+    fn from(e: OllamaError) -> Self {
+        match e {
+            OllamaError::Connect(_) | OllamaError::Timeout => {
+                AppError::OllamaUnreachable(e.to_string())
+            }
+            OllamaError::Status {
+                status: 404,
+                message,
+            } => AppError::RefineModelNotFound(message),
+            e => AppError::Refinement(e.to_string()),
+        }
+    }
+}
+
+/// Errors from a `RefinementProvider`; Ollama's typed errors keep their meaning.
+impl From<Box<dyn Error + Send + Sync>> for AppError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        match e.downcast::<OllamaError>() {
+            Ok(e) => (*e).into(),
+            Err(e) => AppError::Refinement(e.to_string()),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(e: PoisonError<T>) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        AppError::Window(e.to_string())
+    }
+}
+
+impl From<hound::Error> for AppError {
+    fn from(e: hound::Error) -> Self {
+        AppError::Audio(e.to_string())
+    }
+}
+
+impl From<arboard::Error> for AppError {
+    fn from(e: arboard::Error) -> Self {
+        AppError::Clipboard(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for AppError {
+    fn from(e: zip::result::ZipError) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
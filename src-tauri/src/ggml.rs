@@ -0,0 +1,259 @@
+use anyhow::{Context, Result, bail};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// "ggml" in little-endian, as written by whisper.cpp's conversion scripts.
+const GGML_MAGIC: u32 = 0x6767_6d6c;
+/// "GGUF" in little-endian. whisper.cpp does not load these, so we call it out explicitly.
+const GGUF_MAGIC: u32 = 0x4655_4747;
+/// ftype is stored as `qnt_version * 1000 + ftype` since ggml quantization v1.
+const GGML_QNT_VERSION_FACTOR: i32 = 1000;
+/// English-only Whisper models have 51864 tokens, multilingual ones 51865 (51866 for large-v3).
+const MULTILINGUAL_VOCAB_SIZE: i32 = 51865;
+
+/// Header information of a Whisper ggml model file.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ModelInfo {
+    pub path: String,
+    pub file_size: u64,
+    pub model_type: String,
+    pub n_vocab: i32,
+    pub n_audio_layer: i32,
+    pub n_text_layer: i32,
+    pub n_mels: i32,
+    pub multilingual: bool,
+    pub quantization: String,
+}
+
+/// Hyperparameters in the order they follow the magic number in the file.
+struct HParams {
+    n_vocab: i32,
+    n_audio_ctx: i32,
+    n_audio_state: i32,
+    n_audio_head: i32,
+    n_audio_layer: i32,
+    n_text_ctx: i32,
+    n_text_state: i32,
+    n_text_head: i32,
+    n_text_layer: i32,
+    n_mels: i32,
+    ftype: i32,
+}
+
+/// Read and validate the ggml header of a Whisper model.
+pub fn read_model_info(path: &str) -> Result<ModelInfo> {
+    let file = File::open(Path::new(path)).context("Failed to open model file")?;
+    let file_size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let hparams = read_header(&mut BufReader::new(file))?;
+
+    Ok(ModelInfo {
+        path: path.to_string(),
+        file_size,
+        model_type: model_type(&hparams).to_string(),
+        n_vocab: hparams.n_vocab,
+        n_audio_layer: hparams.n_audio_layer,
+        n_text_layer: hparams.n_text_layer,
+        n_mels: hparams.n_mels,
+        multilingual: hparams.n_vocab >= MULTILINGUAL_VOCAB_SIZE,
+        quantization: quantization_name(hparams.ftype % GGML_QNT_VERSION_FACTOR)?.to_string(),
+    })
+}
+
+/// Check the magic number and read the hyperparameters that follow it.
+fn read_header(reader: &mut impl Read) -> Result<HParams> {
+    let magic = read_u32(reader).context("Model file is too small to be a ggml model")?;
+    if magic == GGUF_MAGIC {
+        bail!("GGUF models are not supported, please use a ggml Whisper model (ggml-*.bin)");
+    }
+    if magic != GGML_MAGIC {
+        bail!("Not a ggml model file (bad magic 0x{:08x})", magic);
+    }
+
+    let hparams = read_hparams(reader).context("Model header is truncated")?;
+    validate_hparams(&hparams)?;
+    Ok(hparams)
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_i32(reader: &mut impl Read) -> std::io::Result<i32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_hparams(reader: &mut impl Read) -> std::io::Result<HParams> {
+    Ok(HParams {
+        n_vocab: read_i32(reader)?,
+        n_audio_ctx: read_i32(reader)?,
+        n_audio_state: read_i32(reader)?,
+        n_audio_head: read_i32(reader)?,
+        n_audio_layer: read_i32(reader)?,
+        n_text_ctx: read_i32(reader)?,
+        n_text_state: read_i32(reader)?,
+        n_text_head: read_i32(reader)?,
+        n_text_layer: read_i32(reader)?,
+        n_mels: read_i32(reader)?,
+        ftype: read_i32(reader)?,
+    })
+}
+
+fn validate_hparams(h: &HParams) -> Result<()> {
+    // Every Whisper variant (including distil and fine-tuned ones) keeps the original tokenizer.
+    if !(51864..=51866).contains(&h.n_vocab) {
+        bail!(
+            "Unexpected vocabulary size {} (not a Whisper model?)",
+            h.n_vocab
+        );
+    }
+    let dims = [
+        h.n_audio_ctx,
+        h.n_audio_state,
+        h.n_audio_head,
+        h.n_audio_layer,
+        h.n_text_ctx,
+        h.n_text_state,
+        h.n_text_head,
+        h.n_text_layer,
+    ];
+    if dims.iter().any(|&d| d <= 0 || d > 8192) {
+        bail!("Model header contains invalid dimensions");
+    }
+    if h.n_mels != 80 && h.n_mels != 128 {
+        bail!("Unexpected number of mel bins {}", h.n_mels);
+    }
+    Ok(())
+}
+
+fn model_type(h: &HParams) -> &'static str {
+    // Same mapping whisper.cpp uses to name the loaded model.
+    match h.n_audio_layer {
+        4 => "tiny",
+        6 => "base",
+        12 => "small",
+        24 => "medium",
+        32 if h.n_text_layer == 4 => "large-v3-turbo",
+        32 if h.n_mels == 128 => "large-v3",
+        32 => "large",
+        _ => "unknown",
+    }
+}
+
+fn quantization_name(ftype: i32) -> Result<&'static str> {
+    // Values of ggml's `enum ggml_ftype`.
+    Ok(match ftype {
+        0 => "f32",
+        1 => "f16",
+        2 => "q4_0",
+        3 => "q4_1",
+        4 => "q4_1_some_f16",
+        7 => "q8_0",
+        8 => "q5_0",
+        9 => "q5_1",
+        10 => "q2_k",
+        11 => "q3_k",
+        12 => "q4_k",
+        13 => "q5_k",
+        14 => "q6_k",
+        _ => bail!("Unsupported quantization type {}", ftype),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header of ggml-base.bin, multilingual and f16.
+    const BASE: [i32; 11] = [51865, 1500, 512, 8, 6, 448, 512, 8, 6, 80, 1];
+
+    fn header(magic: u32, hparams: &[i32]) -> Vec<u8> {
+        let mut bytes = magic.to_le_bytes().to_vec();
+        for value in hparams {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        match read_header(&mut &bytes[..]) {
+            Ok(_) => panic!("header was accepted"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn valid_header_is_read() {
+        let h = read_header(&mut &header(GGML_MAGIC, &BASE)[..]).unwrap();
+        assert_eq!(h.n_vocab, 51865);
+        assert_eq!(h.n_audio_layer, 6);
+        assert_eq!(h.n_mels, 80);
+        assert_eq!(model_type(&h), "base");
+        assert_eq!(
+            quantization_name(h.ftype % GGML_QNT_VERSION_FACTOR).unwrap(),
+            "f16"
+        );
+    }
+
+    #[test]
+    fn model_info_is_read_from_the_file() {
+        // large-v3-turbo: 128 mel bins, 4 decoder layers, quantized with ggml v2.
+        let hparams = [51866, 1500, 1280, 20, 32, 448, 1280, 20, 4, 128, 2007];
+        let path = std::env::temp_dir().join(format!("opensw-ggml-{}.bin", std::process::id()));
+        let mut bytes = header(GGML_MAGIC, &hparams);
+        bytes.extend_from_slice(&[0; 16]);
+        std::fs::write(&path, &bytes).unwrap();
+        let info = read_model_info(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        let info = info.unwrap();
+        assert_eq!(info.file_size, bytes.len() as u64);
+        assert_eq!(info.model_type, "large-v3-turbo");
+        assert_eq!(info.quantization, "q8_0");
+        assert!(info.multilingual);
+    }
+
+    #[test]
+    fn truncated_headers_are_rejected() {
+        assert!(error(&[0x6c, 0x6d]).contains("too small"));
+        let full = header(GGML_MAGIC, &BASE);
+        assert!(error(&full[..full.len() - 1]).contains("truncated"));
+        assert!(error(&full[..4]).contains("truncated"));
+    }
+
+    #[test]
+    fn wrong_magic_is_rejected() {
+        assert!(error(&header(GGUF_MAGIC, &BASE)).starts_with("GGUF models are not supported"));
+        assert!(error(&header(0x1234_5678, &BASE)).contains("bad magic 0x12345678"));
+        // The magic is little-endian; big-endian "ggml" is not accepted.
+        assert!(error(&header(GGML_MAGIC.swap_bytes(), &BASE)).contains("bad magic"));
+    }
+
+    #[test]
+    fn implausible_hparams_are_rejected() {
+        let mut vocab = BASE;
+        vocab[0] = 32000;
+        assert!(error(&header(GGML_MAGIC, &vocab)).contains("vocabulary size 32000"));
+
+        let mut dims = BASE;
+        dims[2] = 0;
+        assert!(error(&header(GGML_MAGIC, &dims)).contains("invalid dimensions"));
+
+        let mut mels = BASE;
+        mels[9] = 40;
+        assert!(error(&header(GGML_MAGIC, &mels)).contains("mel bins 40"));
+    }
+
+    #[test]
+    fn unknown_quantization_is_rejected() {
+        assert!(quantization_name(5).is_err());
+        assert_eq!(
+            quantization_name(1002 % GGML_QNT_VERSION_FACTOR).unwrap(),
+            "q4_0"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::AppError;
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry, fmt, reload};
+
+const DEFAULT_LEVEL: &str = "info";
+const LOG_PREFIX: &str = "opensw";
+/// Daily files kept before the oldest is deleted.
+const MAX_LOG_FILES: usize = 7;
+
+/// Handle to the global subscriber, kept in managed state.
+pub struct LogState {
+    filter: reload::Handle<EnvFilter, Registry>,
+    level: std::sync::Mutex<String>,
+    /// Where log files go; `None` when logging to stderr only.
+    pub dir: Option<PathBuf>,
+    /// Flushes the background writer when the app exits.
+    _guard: WorkerGuard,
+}
+
+/// Log to a daily rotating file in `dir`, and to stderr in debug builds.
+/// `RUST_LOG` overrides the default level.
+///
+/// Neither a missing or unwritable `dir` nor an invalid `RUST_LOG` stops the app: it
+/// logs to stderr, or at the default level, and says so once the subscriber is up.
+pub fn init(dir: Option<&Path>) -> anyhow::Result<LogState> {
+    let mut warnings = Vec::new();
+
+    let (level, filter) = initial_filter(std::env::var("RUST_LOG").ok()).unwrap_or_else(|e| {
+        warnings.push(e);
+        (DEFAULT_LEVEL.to_string(), EnvFilter::new(DEFAULT_LEVEL))
+    });
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let file = dir
+        .ok_or_else(|| "no log directory on this platform".to_string())
+        .and_then(|dir| file_appender(dir).map(|appender| (dir, appender)));
+    let (dir, (writer, guard)) = match file {
+        Ok((dir, appender)) => (Some(dir), tracing_appender::non_blocking(appender)),
+        Err(e) => {
+            warnings.push(format!("Logging to stderr only: {}", e));
+            (None, tracing_appender::non_blocking(std::io::stderr()))
+        }
+    };
+    // The file layer already writes to stderr when there's no file.
+    let echo = cfg!(debug_assertions) && dir.is_some();
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(writer).with_ansi(false))
+        .with(echo.then(|| fmt::layer().with_writer(std::io::stderr)))
+        .try_init()?;
+    for warning in warnings {
+        tracing::warn!("{}", warning);
+    }
+
+    Ok(LogState {
+        filter: handle,
+        level: std::sync::Mutex::new(level),
+        dir: dir.map(Path::to_path_buf),
+        _guard: guard,
+    })
+}
+
+/// The filter for `RUST_LOG`, or the default level when it's unset.
+fn initial_filter(rust_log: Option<String>) -> Result<(String, EnvFilter), String> {
+    let level = rust_log.unwrap_or_else(|| DEFAULT_LEVEL.to_string());
+    match EnvFilter::try_new(&level) {
+        Ok(filter) => Ok((level, filter)),
+        Err(e) => Err(format!(
+            "Ignoring RUST_LOG '{}' ({}), using '{}'",
+            level, e, DEFAULT_LEVEL
+        )),
+    }
+}
+
+fn file_appender(dir: &Path) -> Result<RollingFileAppender, String> {
+    let error = |e: &dyn std::fmt::Display| format!("can't write to {}: {}", dir.display(), e);
+    std::fs::create_dir_all(dir).map_err(|e| error(&e))?;
+    RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)
+        .map_err(|e| error(&e))
+}
+
+impl LogState {
+    pub fn level(&self) -> String {
+        self.level
+            .lock()
+            .map(|l| l.clone())
+            .unwrap_or_else(|_| DEFAULT_LEVEL.to_string())
+    }
+
+    /// Log files, oldest first.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let Some(dir) = &self.dir else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.starts_with(LOG_PREFIX))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+        files
+    }
+}
+
+#[tauri::command]
+pub fn get_log_level(state: State<LogState>) -> String {
+    state.level()
+}
+
+/// Change the log level without restarting, e.g. "debug" or "info,opensw=trace".
+#[tauri::command]
+pub fn set_log_level(state: State<LogState>, level: String) -> Result<(), AppError> {
+    let filter =
+        EnvFilter::try_new(&level).map_err(|e| AppError::InvalidLogLevel(e.to_string()))?;
+    state.filter.reload(filter).map_err(AppError::internal)?;
+    *state.level.lock()? = level;
+    tracing::info!(level = %state.level(), "log level changed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_log_overrides_the_default_level() {
+        let (level, _) = initial_filter(Some("debug,opensw=trace".to_string())).unwrap();
+        assert_eq!(level, "debug,opensw=trace");
+        let (level, _) = initial_filter(None).unwrap();
+        assert_eq!(level, DEFAULT_LEVEL);
+    }
+
+    #[test]
+    fn invalid_rust_log_is_reported() {
+        let error = initial_filter(Some("opensw=loud".to_string())).unwrap_err();
+        assert!(error.contains("opensw=loud"));
+    }
+
+    #[test]
+    fn unwritable_log_dir_is_reported() {
+        // A regular file where the directory should be.
+        let file = std::env::temp_dir().join(format!("opensw-log-test-{}", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        let error = file_appender(&file.join("logs")).unwrap_err();
+        std::fs::remove_file(&file).unwrap();
+        assert!(error.starts_with("can't write to "));
+    }
+}
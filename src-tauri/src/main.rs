@@ -10,18 +10,32 @@ fn greet(name: &str) -> String {
 mod audio;
 mod audio_utils;
 mod clipboard;
+mod diagnostics;
+mod error;
+mod ggml;
+mod logging;
+mod meter;
 mod ollama;
+mod refine;
+mod speech;
+mod templates;
 mod tray;
 
 use audio::AudioState;
+use error::AppError;
+use ollama::{OllamaConfig, OllamaState};
+use refine::guard::{self, RefinePath, RefineResult};
+use refine::{ProviderConfig, RefineState};
+use speech::{EngineCapabilities, EngineConfig, SpeechBackend, SpeechState, TranscribeOptions};
 use tauri::{Emitter, Manager};
 use tauri_plugin_dialog::DialogExt;
+use templates::{TemplateStore, TemplateVars};
 
 #[tauri::command]
 async fn select_model(
     app: tauri::AppHandle,
     state: tauri::State<'_, AudioState>,
-) -> Result<String, String> {
+) -> Result<ggml::ModelInfo, AppError> {
     let file_path = app
         .dialog()
         .file()
@@ -30,27 +44,34 @@ async fn select_model(
 
     if let Some(path) = file_path {
         let path_str = path.as_path().map_or_else(
-            || Err("Invalid path".to_string()),
+            || Err(AppError::Io("Invalid path".to_string())),
             |p| Ok(p.to_string_lossy().to_string()),
         )?;
-        *state.model_path.lock().map_err(|e| e.to_string())? = Some(path_str.clone());
-        Ok(path_str)
+        let info = ggml::read_model_info(&path_str)
+            .map_err(|e| AppError::InvalidModel(format!("{:#}", e)))?;
+        *state.model_path.lock()? = Some(path_str);
+        Ok(info)
     } else {
-        Err("No file selected".to_string())
+        Err(AppError::NoFileSelected)
     }
 }
 
 #[tauri::command]
-async fn load_model(state: tauri::State<'_, AudioState>, path: String) -> Result<String, String> {
+async fn load_model(
+    state: tauri::State<'_, AudioState>,
+    path: String,
+) -> Result<ggml::ModelInfo, AppError> {
     use std::path::Path;
     if path.is_empty() {
-        return Err("Empty path provided".to_string());
+        return Err(AppError::NoModelSelected);
     }
     if !Path::new(&path).exists() {
-        return Err(format!("Model file not found: {}", path));
+        return Err(AppError::ModelNotFound(path));
     }
-    *state.model_path.lock().map_err(|e| e.to_string())? = Some(path.clone());
-    Ok(path)
+    let info =
+        ggml::read_model_info(&path).map_err(|e| AppError::InvalidModel(format!("{:#}", e)))?;
+    *state.model_path.lock()? = Some(path);
+    Ok(info)
 }
 
 #[derive(serde::Serialize)]
@@ -61,7 +82,7 @@ struct ComputeDevice {
 
 #[tauri::command]
 #[allow(clippy::collapsible_if)]
-async fn get_compute_devices() -> Result<Vec<ComputeDevice>, String> {
+async fn get_compute_devices() -> Result<Vec<ComputeDevice>, AppError> {
     let mut devices = vec![ComputeDevice {
         name: "CPU".to_string(),
         device_type: "cpu".to_string(),
@@ -96,128 +117,281 @@ async fn get_compute_devices() -> Result<Vec<ComputeDevice>, String> {
 #[tauri::command]
 async fn transcribe_audio(
     state: tauri::State<'_, AudioState>,
+    speech: tauri::State<'_, SpeechState>,
     language: Option<String>,
     use_gpu: bool,
-) -> Result<String, String> {
-    let model_path_opt = state.model_path.lock().map_err(|e| e.to_string())?.clone();
-    let model_path = model_path_opt.ok_or("No model selected")?;
-
-    // Load model
-    let mut params = whisper_rs::WhisperContextParameters::default();
-    params.use_gpu(use_gpu);
-
-    let ctx = whisper_rs::WhisperContext::new_with_params(&model_path, params)
-        .map_err(|e| e.to_string())?;
-    let mut state_w = ctx.create_state().map_err(|e| e.to_string())?;
+    backend: Option<SpeechBackend>,
+    dsp: Option<audio_utils::DspSettings>,
+) -> Result<String, AppError> {
+    let model_path = state.model_path.lock()?.clone();
+    let backend = backend.unwrap_or_default();
+    if matches!(backend, SpeechBackend::Whisper) && model_path.is_none() {
+        return Err(AppError::NoModelSelected);
+    }
+    let config =
+        EngineConfig::new(backend, model_path, use_gpu).map_err(AppError::transcription)?;
 
     // Read audio
-    let temp_dir = std::env::temp_dir();
-    let audio_path = temp_dir.join("recording_test.wav");
+    let audio_path = audio::recording_path();
     let audio_path_str = audio_path.to_string_lossy().to_string();
 
-    let samples = audio_utils::read_and_resample(&audio_path_str).map_err(|e| e.to_string())?;
+    let selection = state.channel_selection.lock()?.clone();
+    let gain = state.gain.lock()?.clone();
+    let dsp = dsp.unwrap_or_default();
+    // Decoding, resampling and the DSP stages take a while on long recordings.
+    let samples = tauri::async_runtime::spawn_blocking(move || {
+        audio_utils::read_and_resample(&audio_path_str, &selection, &gain, &dsp)
+            .map_err(AppError::transcription)
+    })
+    .await
+    .map_err(AppError::internal)??;
+
+    let options = TranscribeOptions {
+        language: Some(language.unwrap_or_else(|| "en".to_string())),
+        ..Default::default()
+    };
+
+    let speech = speech.handle();
+    let started = std::time::Instant::now();
+    let seconds = samples.len() as f32 / 16000.0;
+    let text = tauri::async_runtime::spawn_blocking(move || {
+        speech
+            .with_engine(config, |engine| engine.transcribe(&samples, &options))
+            .map_err(AppError::transcription)
+    })
+    .await
+    .map_err(AppError::internal)?;
+    match &text {
+        Ok(_) => tracing::info!(seconds, elapsed = ?started.elapsed(), "transcribed"),
+        Err(e) => tracing::error!("{}", e),
+    }
+    text
+}
 
-    // Run inference
-    let mut params =
-        whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 1 });
+#[tauri::command]
+fn get_speech_capabilities(speech: tauri::State<'_, SpeechState>) -> Option<EngineCapabilities> {
+    speech.capabilities()
+}
 
-    let lang_str = language.unwrap_or_else(|| "en".to_string());
-    params.set_language(Some(&lang_str));
+#[tauri::command]
+async fn get_refinement_models(
+    ollama: tauri::State<'_, OllamaState>,
+    provider: ProviderConfig,
+) -> Result<Vec<String>, AppError> {
+    let provider = provider.build(&ollama)?;
+    Ok(provider.list_models().await?)
+}
 
-    state_w.full(params, &samples).map_err(|e| e.to_string())?;
+#[derive(serde::Serialize)]
+struct OllamaStatus {
+    version: String,
+    /// Whether `model` is installed; `None` when no model was asked about.
+    model_installed: Option<bool>,
+}
 
-    let mut text = String::new();
-    for segment in state_w.as_iter() {
-        let segment_text = segment.to_str_lossy().map_err(|e| e.to_string())?;
-        text.push_str(segment_text.as_ref());
-    }
+#[tauri::command]
+async fn get_ollama_status(
+    ollama: tauri::State<'_, OllamaState>,
+    config: OllamaConfig,
+    model: Option<String>,
+) -> Result<OllamaStatus, AppError> {
+    let client = ollama.client(&config)?;
+    let version = client.version().await?;
+    let model_installed = match model.filter(|m| !m.is_empty()) {
+        Some(model) => {
+            let installed = client.models().await?;
+            // "llama3.2" and "llama3.2:latest" are the same model.
+            let tagged = format!("{}:latest", model);
+            Some(installed.iter().any(|m| *m == model || *m == tagged))
+        }
+        None => None,
+    };
+    Ok(OllamaStatus {
+        version,
+        model_installed,
+    })
+}
 
-    Ok(text)
+/// Download a model, emitting `ollama-pull-progress` for every status update.
+#[tauri::command]
+async fn pull_ollama_model(
+    window: tauri::WebviewWindow,
+    ollama: tauri::State<'_, OllamaState>,
+    config: OllamaConfig,
+    model: String,
+) -> Result<(), AppError> {
+    let client = ollama.client(&config)?;
+    client
+        .pull(&model, &mut |progress| {
+            let _ = window.emit("ollama-pull-progress", progress);
+        })
+        .await?;
+    Ok(())
 }
 
+/// Load the refinement model ahead of the first dictation, using the active template's
+/// options so Ollama doesn't reload it with a different context size later.
 #[tauri::command]
-async fn get_ollama_models(base_url: String) -> Result<Vec<String>, String> {
-    let url = base_url.clone();
-    tauri::async_runtime::spawn_blocking(move || {
-        ollama::get_models(&url).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
+async fn preload_ollama_model(
+    ollama: tauri::State<'_, OllamaState>,
+    templates: tauri::State<'_, TemplateStore>,
+    config: OllamaConfig,
+    model: String,
+) -> Result<(), AppError> {
+    let options = templates
+        .resolve(None)
+        .map(|t| t.options)
+        .unwrap_or_default();
+    let client = ollama.client(&config)?;
+    client.preload(&model, &options).await?;
+    Ok(())
+}
+
+/// Longest a refinement may take before the raw transcript is used instead, unless the
+/// template sets its own.
+const DEFAULT_REFINE_TIMEOUT_SECS: u64 = 60;
+
+#[derive(Clone, serde::Serialize)]
+struct RefinementTokenEvent {
+    token: String,
 }
 
 #[tauri::command]
-async fn refine_text_with_ollama(
-    base_url: String,
+#[allow(clippy::too_many_arguments)]
+async fn refine_text(
+    window: tauri::WebviewWindow,
+    state: tauri::State<'_, RefineState>,
+    ollama: tauri::State<'_, OllamaState>,
+    templates: tauri::State<'_, TemplateStore>,
+    provider: ProviderConfig,
     text: String,
     model: String,
-    prompt: String,
-) -> Result<String, String> {
-    tauri::async_runtime::spawn_blocking(move || {
-        let final_prompt = prompt.replace("{text}", &text);
-        ollama::generate(&base_url, &model, &final_prompt).map_err(|e| e.to_string())
-    })
-    .await
-    .map_err(|e| e.to_string())?
+    language: Option<String>,
+    template_id: Option<String>,
+) -> Result<RefineResult, AppError> {
+    let template = templates
+        .resolve(template_id.as_deref())
+        .ok_or_else(|| AppError::TemplateNotFound(template_id.unwrap_or_default()))?;
+
+    let previous_transcript =
+        std::mem::replace(&mut *templates.previous_transcript.lock()?, text.clone());
+    let app_name = templates.app_name.lock()?.clone();
+    let vars = TemplateVars {
+        text: &text,
+        language: language.as_deref().unwrap_or("en"),
+        app_name: &app_name,
+        previous_transcript: &previous_transcript,
+    };
+    let final_prompt = template.to_prompt(&vars);
+
+    let cancel = state.start();
+
+    let check_divergence = template.check_divergence && !template.options.json;
+    let timeout = template
+        .options
+        .timeout_secs
+        .filter(|&secs| secs > 0)
+        .unwrap_or(DEFAULT_REFINE_TIMEOUT_SECS);
+    let provider = provider.build(&ollama)?;
+    let mut on_token = |token: &str| {
+        let _ = window.emit(
+            "refinement-token",
+            RefinementTokenEvent {
+                token: token.to_string(),
+            },
+        );
+        !cancel.is_cancelled()
+    };
+    let generation =
+        provider.generate_stream(&model, &final_prompt, &template.options, &mut on_token);
+
+    // Dropping the request on timeout closes the connection, which stops the server too.
+    let result = match tokio::time::timeout(std::time::Duration::from_secs(timeout), generation)
+        .await
+    {
+        Ok(Ok(output)) => {
+            let stopped = cancel.is_cancelled();
+            guard::check(&text, &output, check_divergence, stopped)
+        }
+        Ok(Err(e)) => RefineResult::fallback(&text, format!("Refinement failed: {}", e)),
+        Err(_) => RefineResult::fallback(&text, format!("Refinement timed out after {}s", timeout)),
+    };
+    match (&result.path, &result.reason) {
+        (RefinePath::Fallback, Some(reason)) => {
+            tracing::warn!(%reason, "refinement fell back to raw transcript")
+        }
+        (_, reason) => tracing::info!(?reason, "refinement done"),
+    }
+    Ok(result)
+}
+
+/// Stop running refinements early; each `refine_text` checks what was generated so far.
+#[tauri::command]
+fn stop_refinement(state: tauri::State<'_, RefineState>) {
+    state.stop();
 }
 
 #[tauri::command]
-fn copy_to_clipboard(text: String) -> Result<(), String> {
-    clipboard::copy_text(&text).map_err(|e| e.to_string())
+fn copy_to_clipboard(text: String) -> Result<(), AppError> {
+    clipboard::copy_text(&text)
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ToggleRecordingEvent {
+    /// Set when a template's own shortcut was pressed.
+    template_id: Option<String>,
 }
 
 #[tauri::command]
-async fn request_toggle_recording(app: tauri::AppHandle) -> Result<(), String> {
+async fn request_toggle_recording(app: tauri::AppHandle) -> Result<(), AppError> {
     if let Some(window) = app.get_webview_window("main") {
-        let _ = window.emit("toggle-recording", ());
+        let _ = window.emit(
+            "toggle-recording",
+            ToggleRecordingEvent { template_id: None },
+        );
     }
     Ok(())
 }
 
 #[tauri::command]
-async fn set_window_mode(app: tauri::AppHandle, mode: String) -> Result<(), String> {
+async fn set_window_mode(app: tauri::AppHandle, mode: String) -> Result<(), AppError> {
     if let Some(window) = app.get_webview_window("main") {
         if mode == "compact" {
             // Compact mode: 400x80, Always on top, No decorations
-            window
-                .set_size(tauri::Size::Logical(tauri::LogicalSize {
-                    width: 400.0,
-                    height: 80.0,
-                }))
-                .map_err(|e| e.to_string())?;
-            window.set_always_on_top(true).map_err(|e| e.to_string())?;
-            window.set_decorations(false).map_err(|e| e.to_string())?;
+            window.set_size(tauri::Size::Logical(tauri::LogicalSize {
+                width: 400.0,
+                height: 80.0,
+            }))?;
+            window.set_always_on_top(true)?;
+            window.set_decorations(false)?;
             // Ensure window is visible (in case it was hidden)
-            window.show().map_err(|e| e.to_string())?;
-            window.set_focus().map_err(|e| e.to_string())?;
+            window.show()?;
+            window.set_focus()?;
         } else {
             // Normal mode: 500x720, Normal behavior, Decorations enabled
-            window
-                .set_size(tauri::Size::Logical(tauri::LogicalSize {
-                    width: 500.0,
-                    height: 750.0,
-                }))
-                .map_err(|e| e.to_string())?;
-            window.set_always_on_top(false).map_err(|e| e.to_string())?;
-            window.set_decorations(true).map_err(|e| e.to_string())?;
+            window.set_size(tauri::Size::Logical(tauri::LogicalSize {
+                width: 500.0,
+                height: 750.0,
+            }))?;
+            window.set_always_on_top(false)?;
+            window.set_decorations(true)?;
         }
     }
     Ok(())
 }
 
 #[tauri::command]
-async fn hide_window(app: tauri::AppHandle) -> Result<(), String> {
+async fn hide_window(app: tauri::AppHandle) -> Result<(), AppError> {
     if let Some(window) = app.get_webview_window("main") {
         // Reset to normal mode before hiding
-        window
-            .set_size(tauri::Size::Logical(tauri::LogicalSize {
-                width: 500.0,
-                height: 750.0,
-            }))
-            .map_err(|e| e.to_string())?;
-        window.set_always_on_top(false).map_err(|e| e.to_string())?;
-        window.set_decorations(true).map_err(|e| e.to_string())?;
+        window.set_size(tauri::Size::Logical(tauri::LogicalSize {
+            width: 500.0,
+            height: 750.0,
+        }))?;
+        window.set_always_on_top(false)?;
+        window.set_decorations(true)?;
         // Hide the window
-        window.hide().map_err(|e| e.to_string())?;
+        window.hide()?;
     }
     Ok(())
 }
@@ -231,21 +405,45 @@ fn main() {
         .plugin(tauri_plugin_process::init())
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
-                .with_handler(|app, _shortcut, event| {
+                .with_handler(|app, shortcut, event| {
                     use tauri_plugin_global_shortcut::ShortcutState;
                     if event.state() == ShortcutState::Pressed
                         && let Some(window) = app.get_webview_window("main")
                     {
-                        let _ = window.emit("toggle-recording", ());
+                        let store = app.state::<TemplateStore>();
+                        // Remember the focused app before our compact window takes focus.
+                        if let Ok(active) = active_win_pos_rs::get_active_window()
+                            && active.process_id != u64::from(std::process::id())
+                            && let Ok(mut app_name) = store.app_name.lock()
+                        {
+                            *app_name = active.app_name;
+                        }
+                        let _ = window.emit(
+                            "toggle-recording",
+                            ToggleRecordingEvent {
+                                template_id: store.template_for_shortcut(shortcut),
+                            },
+                        );
                     }
                 })
                 .build(),
         )
         .manage(AudioState::new())
+        .manage(SpeechState::new())
+        .manage(RefineState::new())
+        .manage(OllamaState::new())
         .setup(|app| {
+            app.manage(logging::init(app.path().app_log_dir().ok().as_deref())?);
+            tracing::info!(version = %app.package_info().version, "starting");
+
+            let templates_path = app.path().app_config_dir()?.join("templates.json");
+            app.manage(TemplateStore::load(templates_path));
+
             // Setup system tray
             tray::setup_tray(app)?;
 
+            audio::watch_input_devices(app.handle().clone());
+
             // Register global shortcut
             #[cfg(desktop)]
             {
@@ -259,7 +457,11 @@ fn main() {
 
                 // Register the shortcut - don't panic if it fails (e.g., if another app uses it)
                 if let Err(e) = app.global_shortcut().register(shortcut) {
-                    eprintln!("Warning: Failed to register global shortcut: {}", e);
+                    tracing::warn!("Failed to register global shortcut: {}", e);
+                }
+
+                if let Err(e) = templates::register_shortcuts(app.handle()) {
+                    tracing::warn!("{}", e);
                 }
             }
 
@@ -279,19 +481,40 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             greet,
             audio::start_recording,
+            audio::pause_recording,
+            audio::resume_recording,
             audio::stop_recording,
             audio::play_recording,
+            audio::get_audio_hosts,
             audio::get_input_devices,
+            audio::get_output_devices,
+            audio::set_device_watch,
+            audio::get_input_configs,
+            audio::set_warm_capture,
             select_model,
             load_model,
             transcribe_audio,
-            get_ollama_models,
-            refine_text_with_ollama,
+            get_speech_capabilities,
+            get_refinement_models,
+            get_ollama_status,
+            pull_ollama_model,
+            preload_ollama_model,
+            refine_text,
+            stop_refinement,
+            templates::list_templates,
+            templates::save_template,
+            templates::delete_template,
+            templates::set_active_template,
+            templates::import_templates,
+            templates::export_templates,
             copy_to_clipboard,
             request_toggle_recording,
             set_window_mode,
             hide_window,
-            get_compute_devices
+            get_compute_devices,
+            logging::get_log_level,
+            logging::set_log_level,
+            diagnostics::export_diagnostics
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
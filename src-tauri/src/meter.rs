@@ -0,0 +1,171 @@
+use crate::audio_utils::{ChannelSelection, Gain, GainSettings};
+use serde::Serialize;
+use std::f32::consts::PI;
+
+/// Level updates per second when the settings don't say otherwise.
+pub const DEFAULT_RATE_HZ: u32 = 30;
+const MAX_RATE_HZ: u32 = 120;
+/// Samples at or above this magnitude count as clipped.
+const CLIP_THRESHOLD: f32 = 0.999;
+/// Quietest level reported, so silence doesn't turn into -inf.
+const FLOOR_DBFS: f32 = -100.0;
+/// Octave band centres for the visualizer, in Hz.
+const BAND_CENTERS: [f32; 7] = [125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0];
+/// One octave wide.
+const BAND_Q: f32 = std::f32::consts::SQRT_2;
+
+/// Payload of `audio-level-update`.
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioLevel {
+    /// RMS of the selected channels mixed down and through the gain stage, as they will
+    /// be transcribed.
+    pub amplitude: f32,
+    pub peak: f32,
+    pub rms_dbfs: f32,
+    pub peak_dbfs: f32,
+    /// Some input channel hit full scale since the last update.
+    pub clipping: bool,
+    /// RMS of each input channel.
+    pub channels: Vec<f32>,
+    /// Level of the mix in each of `BAND_CENTERS`, in dBFS.
+    pub bands: Vec<f32>,
+}
+
+/// Accumulates levels over callbacks and reports them at a fixed rate instead of once
+/// per callback.
+pub struct Meter {
+    channels: usize,
+    selection: ChannelSelection,
+    gain: Gain,
+    /// Frames between two reports.
+    interval: usize,
+    frames: usize,
+    channel_sums: Vec<f32>,
+    mix_sum: f32,
+    peak: f32,
+    clipping: bool,
+    bands: Vec<BandFilter>,
+}
+
+impl Meter {
+    pub fn new(
+        sample_rate: u32,
+        channels: usize,
+        selection: ChannelSelection,
+        gain: &GainSettings,
+        rate_hz: u32,
+    ) -> Self {
+        let rate_hz = rate_hz.clamp(1, MAX_RATE_HZ);
+        Self {
+            channels: channels.max(1),
+            selection,
+            gain: Gain::new(gain, sample_rate),
+            interval: (sample_rate / rate_hz).max(1) as usize,
+            frames: 0,
+            channel_sums: vec![0.0; channels.max(1)],
+            mix_sum: 0.0,
+            peak: 0.0,
+            clipping: false,
+            bands: BAND_CENTERS
+                .iter()
+                .map(|&center| BandFilter::new(center, sample_rate as f32))
+                .collect(),
+        }
+    }
+
+    /// Feed interleaved samples. Returns a level each time a full interval has passed.
+    pub fn process(&mut self, input: &[f32]) -> Option<AudioLevel> {
+        let mut level = None;
+        for frame in input.chunks_exact(self.channels) {
+            for (sum, &x) in self.channel_sums.iter_mut().zip(frame) {
+                *sum += x * x;
+                self.clipping |= x.abs() >= CLIP_THRESHOLD;
+            }
+            let mono = self.gain.process(self.selection.downmix(frame));
+            self.mix_sum += mono * mono;
+            self.peak = self.peak.max(mono.abs());
+            for band in &mut self.bands {
+                band.process(mono);
+            }
+
+            self.frames += 1;
+            if self.frames >= self.interval {
+                level = Some(self.report());
+            }
+        }
+        level
+    }
+
+    fn report(&mut self) -> AudioLevel {
+        let frames = self.frames as f32;
+        let amplitude = (self.mix_sum / frames).sqrt();
+        let level = AudioLevel {
+            amplitude,
+            peak: self.peak,
+            rms_dbfs: dbfs(amplitude),
+            peak_dbfs: dbfs(self.peak),
+            clipping: self.clipping,
+            channels: self
+                .channel_sums
+                .iter()
+                .map(|sum| (sum / frames).sqrt())
+                .collect(),
+            bands: self
+                .bands
+                .iter_mut()
+                .map(|band| dbfs((std::mem::take(&mut band.sum) / frames).sqrt()))
+                .collect(),
+        };
+
+        self.frames = 0;
+        self.channel_sums.iter_mut().for_each(|sum| *sum = 0.0);
+        self.mix_sum = 0.0;
+        self.peak = 0.0;
+        self.clipping = false;
+        level
+    }
+}
+
+fn dbfs(level: f32) -> f32 {
+    (20.0 * level.log10()).max(FLOOR_DBFS)
+}
+
+/// RBJ band-pass biquad with 0 dB peak gain, accumulating the energy it lets through.
+struct BandFilter {
+    b0: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+    sum: f32,
+    /// Bands at or above Nyquist stay silent.
+    enabled: bool,
+}
+
+impl BandFilter {
+    fn new(center: f32, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * center / sample_rate;
+        let alpha = w0.sin() / (2.0 * BAND_Q);
+        let a0 = 1.0 + alpha;
+        Self {
+            b0: alpha / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha) / a0,
+            z1: 0.0,
+            z2: 0.0,
+            sum: 0.0,
+            enabled: center < sample_rate * 0.45,
+        }
+    }
+
+    /// Transposed direct form II; b1 is zero and b2 is -b0 for this filter.
+    fn process(&mut self, x: f32) {
+        if !self.enabled {
+            return;
+        }
+        let y = self.b0 * x + self.z1;
+        self.z1 = -self.a1 * y + self.z2;
+        self.z2 = -self.b0 * x - self.a2 * y;
+        self.sum += y * y;
+    }
+}
@@ -1,8 +1,93 @@
-use reqwest::blocking::Client;
+use crate::refine::{ChatMessage, GenerationOptions, RefinePrompt, RefinementProvider};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+/// How to reach the Ollama server. The client is rebuilt whenever this changes.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    /// Extra headers sent with every request, e.g. for an auth proxy in front of Ollama.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// HTTP(S) or SOCKS proxy URL. Without it the system proxy settings apply.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    /// Limit for a whole non-streaming request, or for the gap between chunks of a stream.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum OllamaError {
+    /// The client couldn't be built from the config (bad header or proxy URL).
+    Config(String),
+    /// The server couldn't be reached.
+    Connect(String),
+    Timeout,
+    /// The server answered with an error status.
+    Status {
+        status: u16,
+        message: String,
+    },
+    /// The response wasn't the JSON we expected.
+    Decode(String),
+    /// Ollama reported an error in the middle of a stream.
+    Server(String),
+}
+
+impl fmt::Display for OllamaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OllamaError::Config(e) => write!(f, "Invalid Ollama settings: {}", e),
+            OllamaError::Connect(e) => write!(f, "Cannot connect to Ollama: {}", e),
+            OllamaError::Timeout => write!(f, "Ollama request timed out"),
+            OllamaError::Status { status, message } => {
+                write!(f, "Ollama returned HTTP {}: {}", status, message)
+            }
+            OllamaError::Decode(e) => write!(f, "Unexpected response from Ollama: {}", e),
+            OllamaError::Server(e) => write!(f, "Ollama error: {}", e),
+        }
+    }
+}
+
+impl Error for OllamaError {}
+
+impl From<reqwest::Error> for OllamaError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            OllamaError::Timeout
+        } else if e.is_decode() {
+            OllamaError::Decode(e.to_string())
+        } else if let Some(status) = e.status() {
+            OllamaError::Status {
+                status: status.as_u16(),
+                message: e.to_string(),
+            }
+        } else {
+            OllamaError::Connect(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for OllamaError {
+    fn from(e: serde_json::Error) -> Self {
+        OllamaError::Decode(e.to_string())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct OllamaModel {
     pub name: String,
@@ -14,10 +99,73 @@ struct OllamaTagsResponse {
 }
 
 #[derive(Serialize, Debug)]
-struct GenerateRequest {
-    model: String,
-    prompt: String,
+struct GenerateRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
     stream: bool,
+    #[serde(flatten)]
+    params: RequestParams<'a>,
+}
+
+/// Fields shared by `/api/generate` and `/api/chat`.
+#[derive(Serialize, Debug)]
+struct RequestParams<'a> {
+    #[serde(skip_serializing_if = "ModelOptions::is_empty")]
+    options: ModelOptions<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+struct ModelOptions<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+}
+
+impl ModelOptions<'_> {
+    fn is_empty(&self) -> bool {
+        self.temperature.is_none()
+            && self.top_p.is_none()
+            && self.num_ctx.is_none()
+            && self.seed.is_none()
+            && self.stop.is_empty()
+    }
+}
+
+impl<'a> From<&'a GenerationOptions> for RequestParams<'a> {
+    fn from(options: &'a GenerationOptions) -> Self {
+        // Ollama takes a number of seconds or a Go duration string ("30m").
+        let keep_alive = options
+            .keep_alive
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| match s.parse::<i64>() {
+                Ok(seconds) => serde_json::Value::from(seconds),
+                Err(_) => serde_json::Value::from(s),
+            });
+        Self {
+            options: ModelOptions {
+                temperature: options.temperature,
+                top_p: options.top_p,
+                num_ctx: options.num_ctx,
+                seed: options.seed,
+                stop: &options.stop,
+            },
+            keep_alive,
+            format: options.json.then_some("json"),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -25,28 +173,389 @@ struct GenerateResponse {
     response: String,
 }
 
-pub fn get_models(base_url: &str) -> Result<Vec<String>, Box<dyn Error>> {
-    let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
-    let url = format!("{}/api/tags", base_url.trim_end_matches('/'));
-    let res = client.get(&url).send()?;
-    let tags_response: OllamaTagsResponse = res.json()?;
+#[derive(Serialize, Debug)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    stream: bool,
+    #[serde(flatten)]
+    params: RequestParams<'a>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatResponse {
+    message: ChatMessage,
+}
+
+/// One line of the NDJSON stream returned by `/api/generate` or `/api/chat` with `stream: true`.
+#[derive(Deserialize, Debug)]
+struct StreamChunk {
+    /// Set by `/api/generate`.
+    #[serde(default)]
+    response: String,
+    /// Set by `/api/chat`.
+    message: Option<ChatMessage>,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+}
+
+impl StreamChunk {
+    fn text(&self) -> &str {
+        self.message
+            .as_ref()
+            .map_or(self.response.as_str(), |m| m.content.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct VersionResponse {
+    version: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Serialize, Debug)]
+struct PullRequest<'a> {
+    model: &'a str,
+    stream: bool,
+}
+
+/// One line of the NDJSON stream returned by `/api/pull`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    pub digest: Option<String>,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    #[serde(skip_serializing)]
+    error: Option<String>,
+}
+
+/// Async client for one Ollama server. Cheap to share; requests reuse pooled connections.
+pub struct OllamaClient {
+    client: Client,
+    config: OllamaConfig,
+}
+
+impl OllamaClient {
+    pub fn new(config: OllamaConfig) -> Result<Self, OllamaError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|e| OllamaError::Config(format!("header '{}': {}", name, e)))?;
+            let value = HeaderValue::from_str(value.trim())
+                .map_err(|e| OllamaError::Config(format!("header '{}': {}", name, e)))?;
+            headers.insert(name, value);
+        }
+
+        // Streams can run far longer than any sensible total timeout, so the client only
+        // limits the gap between reads; non-streaming requests add a total limit.
+        let mut builder = Client::builder()
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(
+                config
+                    .connect_timeout_secs
+                    .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+            ))
+            .read_timeout(Self::timeout(&config));
+        if let Some(proxy) = config.proxy.as_deref().filter(|p| !p.trim().is_empty()) {
+            let proxy = reqwest::Proxy::all(proxy.trim())
+                .map_err(|e| OllamaError::Config(format!("proxy: {}", e)))?;
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| OllamaError::Config(e.to_string()))?;
+
+        Ok(Self { client, config })
+    }
+
+    fn timeout(config: &OllamaConfig) -> Duration {
+        Duration::from_secs(config.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.base_url.trim_end_matches('/'), path)
+    }
+
+    /// Send a request, turning an error status into [`OllamaError::Status`] with the
+    /// message from Ollama's `{"error": ...}` body when there is one.
+    async fn send(req: RequestBuilder) -> Result<Response, OllamaError> {
+        let res = req.send().await?;
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        let body = res.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<ErrorResponse>(&body)
+            .map(|e| e.error)
+            .unwrap_or(body);
+        Err(OllamaError::Status {
+            status: status.as_u16(),
+            message,
+        })
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, OllamaError> {
+        let req = self
+            .client
+            .get(self.url(path))
+            .timeout(Self::timeout(&self.config));
+        Ok(Self::send(req).await?.json().await?)
+    }
+
+    async fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, OllamaError> {
+        let req = self
+            .client
+            .post(self.url(path))
+            .json(body)
+            .timeout(Self::timeout(&self.config));
+        Ok(Self::send(req).await?.json().await?)
+    }
+
+    /// Call `on_line` for every NDJSON line of a streaming response until it returns `false`.
+    async fn post_stream<B: Serialize>(
+        &self,
+        path: &str,
+        body: &B,
+        on_line: &mut (dyn FnMut(&str) -> Result<bool, OllamaError> + Send),
+    ) -> Result<(), OllamaError> {
+        let mut res = Self::send(self.client.post(self.url(path)).json(body)).await?;
+        let mut buffer = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if !line.trim().is_empty() && !on_line(&line)? {
+                    return Ok(());
+                }
+            }
+        }
+        let rest = String::from_utf8_lossy(&buffer);
+        if !rest.trim().is_empty() {
+            on_line(&rest)?;
+        }
+        Ok(())
+    }
+
+    /// Server version from `/api/version`; fails if Ollama isn't running.
+    pub async fn version(&self) -> Result<String, OllamaError> {
+        let version: VersionResponse = self.get_json("/api/version").await?;
+        Ok(version.version)
+    }
+
+    pub async fn models(&self) -> Result<Vec<String>, OllamaError> {
+        let tags: OllamaTagsResponse = self.get_json("/api/tags").await?;
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+
+    pub async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: &GenerationOptions,
+    ) -> Result<String, OllamaError> {
+        let req = GenerateRequest {
+            model,
+            prompt,
+            stream: false,
+            params: options.into(),
+        };
+        let res: GenerateResponse = self.post_json("/api/generate", &req).await?;
+        Ok(res.response)
+    }
+
+    /// Stream a completion, calling `on_token` for every chunk as it arrives.
+    /// Returning `false` from `on_token` closes the connection, which stops Ollama generating,
+    /// and the text received so far is returned.
+    pub async fn generate_stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        options: &GenerationOptions,
+        on_token: &mut (dyn FnMut(&str) -> bool + Send),
+    ) -> Result<String, OllamaError> {
+        let req = GenerateRequest {
+            model,
+            prompt,
+            stream: true,
+            params: options.into(),
+        };
+        self.read_stream("/api/generate", &req, on_token).await
+    }
+
+    pub async fn chat(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        options: &GenerationOptions,
+    ) -> Result<String, OllamaError> {
+        let req = ChatRequest {
+            model,
+            messages,
+            stream: false,
+            params: options.into(),
+        };
+        let res: ChatResponse = self.post_json("/api/chat", &req).await?;
+        Ok(res.message.content)
+    }
+
+    /// Streaming variant of [`Self::chat`], see [`Self::generate_stream`].
+    pub async fn chat_stream(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        options: &GenerationOptions,
+        on_token: &mut (dyn FnMut(&str) -> bool + Send),
+    ) -> Result<String, OllamaError> {
+        let req = ChatRequest {
+            model,
+            messages,
+            stream: true,
+            params: options.into(),
+        };
+        self.read_stream("/api/chat", &req, on_token).await
+    }
+
+    async fn read_stream<B: Serialize>(
+        &self,
+        path: &str,
+        body: &B,
+        on_token: &mut (dyn FnMut(&str) -> bool + Send),
+    ) -> Result<String, OllamaError> {
+        let mut text = String::new();
+        self.post_stream(path, body, &mut |line| {
+            let chunk: StreamChunk = serde_json::from_str(line)?;
+            if let Some(error) = chunk.error {
+                return Err(OllamaError::Server(error));
+            }
+            text.push_str(chunk.text());
+            Ok(on_token(chunk.text()) && !chunk.done)
+        })
+        .await?;
+        Ok(text)
+    }
+
+    /// Download `model`, reporting each status line from `/api/pull` as it arrives.
+    pub async fn pull(
+        &self,
+        model: &str,
+        on_progress: &mut (dyn FnMut(&PullProgress) + Send),
+    ) -> Result<(), OllamaError> {
+        let req = PullRequest {
+            model,
+            stream: true,
+        };
+        self.post_stream("/api/pull", &req, &mut |line| {
+            let progress: PullProgress = serde_json::from_str(line)?;
+            if let Some(error) = progress.error {
+                return Err(OllamaError::Server(error));
+            }
+            on_progress(&progress);
+            Ok(true)
+        })
+        .await
+    }
+
+    /// Load `model` into memory without generating anything, so the first refinement
+    /// doesn't pay the load time. `options` should match the ones used for refinement:
+    /// a different `num_ctx` makes Ollama reload the model.
+    pub async fn preload(
+        &self,
+        model: &str,
+        options: &GenerationOptions,
+    ) -> Result<(), OllamaError> {
+        self.generate(model, "", options).await.map(|_| ())
+    }
+}
+
+/// The shared client, kept in managed state so connections are pooled across commands.
+pub struct OllamaState {
+    client: Mutex<Option<Arc<OllamaClient>>>,
+}
+
+impl OllamaState {
+    pub fn new() -> Self {
+        Self {
+            client: Mutex::new(None),
+        }
+    }
+
+    /// Client for `config`, reusing the current one unless the settings changed.
+    pub fn client(&self, config: &OllamaConfig) -> Result<Arc<OllamaClient>, OllamaError> {
+        let mut current = self
+            .client
+            .lock()
+            .map_err(|e| OllamaError::Config(e.to_string()))?;
+        if let Some(client) = current.as_ref()
+            && client.config == *config
+        {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(OllamaClient::new(config.clone())?);
+        *current = Some(client.clone());
+        Ok(client)
+    }
+}
+
+pub struct OllamaProvider {
+    client: Arc<OllamaClient>,
+}
 
-    Ok(tags_response.models.into_iter().map(|m| m.name).collect())
+impl OllamaProvider {
+    pub fn new(client: Arc<OllamaClient>) -> Self {
+        Self { client }
+    }
 }
 
-pub fn generate(base_url: &str, model: &str, prompt: &str) -> Result<String, Box<dyn Error>> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(120))
-        .build()?;
-    let req = GenerateRequest {
-        model: model.to_string(),
-        prompt: prompt.to_string(),
-        stream: false,
-    };
+#[async_trait]
+impl RefinementProvider for OllamaProvider {
+    async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        Ok(self.client.models().await?)
+    }
 
-    let url = format!("{}/api/generate", base_url.trim_end_matches('/'));
-    let res = client.post(&url).json(&req).send()?;
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &RefinePrompt,
+        options: &GenerationOptions,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        Ok(match prompt {
+            RefinePrompt::Completion(prompt) => {
+                self.client.generate(model, prompt, options).await?
+            }
+            RefinePrompt::Chat(messages) => self.client.chat(model, messages, options).await?,
+        })
+    }
 
-    let gen_response: GenerateResponse = res.json()?;
-    Ok(gen_response.response)
+    async fn generate_stream(
+        &self,
+        model: &str,
+        prompt: &RefinePrompt,
+        options: &GenerationOptions,
+        on_token: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        Ok(match prompt {
+            RefinePrompt::Completion(prompt) => {
+                self.client
+                    .generate_stream(model, prompt, options, on_token)
+                    .await?
+            }
+            RefinePrompt::Chat(messages) => {
+                self.client
+                    .chat_stream(model, messages, options, on_token)
+                    .await?
+            }
+        })
+    }
 }
@@ -0,0 +1,223 @@
+pub mod guard;
+mod openai;
+
+use crate::ollama::{OllamaConfig, OllamaError, OllamaProvider, OllamaState};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+pub use openai::OpenAiProvider;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    fn new(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+}
+
+/// A raw transcript and how it should look after cleaning.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FewShotExample {
+    pub input: String,
+    pub output: String,
+}
+
+pub enum RefinePrompt {
+    /// A single prompt with the transcript already substituted for `{text}`.
+    Completion(String),
+    Chat(Vec<ChatMessage>),
+}
+
+impl RefinePrompt {
+    /// System instructions, then each example as a user/assistant turn, then the transcript.
+    /// Keeping the transcript in its own user message stops the model from treating
+    /// dictated questions as questions addressed to it.
+    pub fn chat(system: &str, examples: &[FewShotExample], text: &str) -> Self {
+        let mut messages = vec![ChatMessage::new("system", system)];
+        for example in examples {
+            messages.push(ChatMessage::new("user", &example.input));
+            messages.push(ChatMessage::new("assistant", &example.output));
+        }
+        messages.push(ChatMessage::new("user", text));
+        RefinePrompt::Chat(messages)
+    }
+
+    /// Messages for providers that only speak chat.
+    pub fn to_messages(&self) -> Vec<ChatMessage> {
+        match self {
+            RefinePrompt::Completion(prompt) => vec![ChatMessage::new("user", prompt)],
+            RefinePrompt::Chat(messages) => messages.clone(),
+        }
+    }
+}
+
+/// Sampling and model-residency settings for a refinement request.
+/// Unset fields are left to the server's defaults.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GenerationOptions {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    /// Context window size; Ollama only.
+    pub num_ctx: Option<u32>,
+    pub seed: Option<i64>,
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// How long Ollama keeps the model loaded after the request, e.g. "30m", or "-1" to
+    /// keep it resident between dictations.
+    pub keep_alive: Option<String>,
+    /// Ask for a JSON object instead of free text.
+    #[serde(default)]
+    pub json: bool,
+    /// Seconds before the raw transcript is used instead; 60 when unset. Not sent to the
+    /// server.
+    pub timeout_secs: Option<u64>,
+}
+
+/// An LLM server that can clean up a transcript.
+#[async_trait]
+pub trait RefinementProvider: Send + Sync {
+    async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>>;
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &RefinePrompt,
+        options: &GenerationOptions,
+    ) -> Result<String, Box<dyn Error + Send + Sync>>;
+
+    /// Like `generate`, but reports text as it is produced. Returning `false` from
+    /// `on_token` stops early and the text received so far is returned.
+    /// Providers without streaming support report the whole response as one token.
+    // The explicit `for<'t>` keeps `async_trait` from tying the callback's argument
+    // lifetime to the method's.
+    async fn generate_stream(
+        &self,
+        model: &str,
+        prompt: &RefinePrompt,
+        options: &GenerationOptions,
+        on_token: &mut (dyn for<'t> FnMut(&'t str) -> bool + Send),
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let text = self.generate(model, prompt, options).await?;
+        on_token(&text);
+        Ok(text)
+    }
+}
+
+/// Lets `stop_refinement` interrupt running `refine_text` calls. Each call has its own
+/// token, so starting a refinement never clears a stop meant for another one.
+pub struct RefineState {
+    running: Mutex<Vec<Weak<AtomicBool>>>,
+}
+
+/// Cancellation flag of one refinement; it stops being tracked when dropped.
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl RefineState {
+    pub fn new() -> Self {
+        Self {
+            running: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A token for a new refinement, which `stop` cancels until it is dropped.
+    pub fn start(&self) -> CancelToken {
+        let token = Arc::new(AtomicBool::new(false));
+        if let Ok(mut running) = self.running.lock() {
+            running.retain(|t| t.strong_count() > 0);
+            running.push(Arc::downgrade(&token));
+        }
+        CancelToken(token)
+    }
+
+    /// Cancel every refinement currently running.
+    pub fn stop(&self) {
+        if let Ok(running) = self.running.lock() {
+            for token in running.iter().filter_map(Weak::upgrade) {
+                token.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+impl Default for RefineState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Provider selected in the settings UI.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProviderConfig {
+    Ollama(OllamaConfig),
+    /// Any OpenAI-compatible chat-completions server (llama.cpp server, LM Studio, vLLM).
+    OpenAi {
+        base_url: String,
+        api_key: Option<String>,
+    },
+}
+
+impl ProviderConfig {
+    /// Ollama providers share the pooled client in `ollama`.
+    pub fn build(self, ollama: &OllamaState) -> Result<Box<dyn RefinementProvider>, OllamaError> {
+        Ok(match self {
+            ProviderConfig::Ollama(config) => {
+                Box::new(OllamaProvider::new(ollama.client(&config)?))
+            }
+            ProviderConfig::OpenAi { base_url, api_key } => Box::new(OpenAiProvider::new(
+                &base_url,
+                api_key.filter(|k| !k.is_empty()),
+            )),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stop_cancels_running_refinements() {
+        let state = RefineState::new();
+        let first = state.start();
+        let second = state.start();
+        state.stop();
+        assert!(first.is_cancelled());
+        assert!(second.is_cancelled());
+    }
+
+    #[test]
+    fn starting_a_refinement_keeps_earlier_stops() {
+        let state = RefineState::new();
+        let stopped = state.start();
+        state.stop();
+        let next = state.start();
+        assert!(stopped.is_cancelled());
+        assert!(!next.is_cancelled());
+    }
+
+    #[test]
+    fn finished_refinements_are_forgotten() {
+        let state = RefineState::new();
+        drop(state.start());
+        let running = state.start();
+        assert_eq!(state.running.lock().unwrap().len(), 1);
+        state.stop();
+        assert!(running.is_cancelled());
+    }
+}
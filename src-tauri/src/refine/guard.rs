@@ -0,0 +1,277 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Below this many characters the ratios are too noisy to judge divergence.
+const MIN_CHECKED_CHARS: usize = 20;
+const MIN_LENGTH_RATIO: f32 = 0.5;
+const MAX_LENGTH_RATIO: f32 = 2.0;
+const MIN_SIMILARITY: f32 = 0.6;
+
+/// Openers models put in front of the cleaned text despite being told not to.
+const PREAMBLE_MARKERS: &[&str] = &[
+    "here is",
+    "here's",
+    "sure",
+    "certainly",
+    "cleaned",
+    "corrected",
+    "revised",
+    "以下",
+    "修正",
+    "整形",
+];
+
+/// Which text `refine_text` handed back.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefinePath {
+    Refined,
+    /// The raw transcript, because refinement failed or its output was rejected.
+    Fallback,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefineResult {
+    pub text: String,
+    pub path: RefinePath,
+    /// Why the fallback was taken, or what was cleaned up on the refined path.
+    pub reason: Option<String>,
+}
+
+impl RefineResult {
+    pub fn fallback(raw: &str, reason: String) -> Self {
+        Self {
+            text: raw.to_string(),
+            path: RefinePath::Fallback,
+            reason: Some(reason),
+        }
+    }
+}
+
+/// Accept `output` as the refined text, or fall back to `raw` if it looks like the model
+/// did something other than clean up the transcript. `stopped` output was cut short by
+/// the user, so it is only compared with as much of the transcript as it covers.
+pub fn check(raw: &str, output: &str, check_divergence: bool, stopped: bool) -> RefineResult {
+    let cleaned = strip_preamble(output);
+    if cleaned.is_empty() {
+        return RefineResult::fallback(raw, "Model returned no text".to_string());
+    }
+
+    if check_divergence && raw.chars().count() >= MIN_CHECKED_CHARS {
+        let reference = if stopped {
+            prefix(raw, cleaned.chars().count())
+        } else {
+            let ratio = cleaned.chars().count() as f32 / raw.chars().count() as f32;
+            if !(MIN_LENGTH_RATIO..=MAX_LENGTH_RATIO).contains(&ratio) {
+                return RefineResult::fallback(
+                    raw,
+                    format!("Output length is {:.1}x the transcript", ratio),
+                );
+            }
+            raw
+        };
+        let similarity = similarity(reference, cleaned);
+        if similarity < MIN_SIMILARITY {
+            return RefineResult::fallback(
+                raw,
+                format!(
+                    "Output is too different from the transcript ({:.2})",
+                    similarity
+                ),
+            );
+        }
+    }
+
+    RefineResult {
+        text: cleaned.to_string(),
+        path: RefinePath::Refined,
+        reason: (cleaned.len() != output.trim().len()).then(|| "Removed preamble".to_string()),
+    }
+}
+
+/// The first `chars` characters of `text`.
+fn prefix(text: &str, chars: usize) -> &str {
+    text.char_indices()
+        .nth(chars)
+        .map_or(text, |(end, _)| &text[..end])
+}
+
+/// Drop a leading "Here is the cleaned text:" line and a surrounding code fence.
+fn strip_preamble(output: &str) -> &str {
+    let mut text = output.trim();
+
+    if let Some((first, rest)) = text.split_once('\n') {
+        let first = first.trim();
+        let lower = first.to_lowercase();
+        if (first.ends_with(':') || first.ends_with('：'))
+            && first.chars().count() <= 80
+            && PREAMBLE_MARKERS.iter().any(|m| lower.contains(m))
+        {
+            text = rest.trim();
+        }
+    }
+
+    if let Some(inner) = text.strip_prefix("```")
+        && let Some(inner) = inner.strip_suffix("```")
+    {
+        // Skip the language tag after the opening fence, if any.
+        text = inner
+            .split_once('\n')
+            .map_or(inner, |(_, body)| body)
+            .trim();
+    }
+
+    text
+}
+
+/// Dice coefficient over character bigrams, ignoring whitespace and punctuation so that
+/// added punctuation and removed fillers don't count as divergence. Works for scripts
+/// without word boundaries, such as Japanese.
+fn similarity(a: &str, b: &str) -> f32 {
+    let a = bigrams(a);
+    let b = bigrams(b);
+    let total: usize = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if total == 0 {
+        return 1.0;
+    }
+    let shared: usize = a
+        .iter()
+        .map(|(pair, count)| (*count).min(b.get(pair).copied().unwrap_or(0)))
+        .sum();
+    2.0 * shared as f32 / total as f32
+}
+
+fn bigrams(text: &str) -> HashMap<(char, char), usize> {
+    let chars: Vec<char> = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let mut counts = HashMap::new();
+    for pair in chars.windows(2) {
+        *counts.entry((pair[0], pair[1])).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &str = "so um I think we should uh move the meeting to thursday afternoon";
+    const CLEANED: &str = "I think we should move the meeting to Thursday afternoon.";
+
+    #[test]
+    fn clean_output_is_accepted() {
+        let result = check(RAW, CLEANED, true, false);
+        assert_eq!(result.path, RefinePath::Refined);
+        assert_eq!(result.text, CLEANED);
+        assert_eq!(result.reason, None);
+    }
+
+    #[test]
+    fn preamble_line_is_removed() {
+        let output = format!("Here is the cleaned text:\n{}", CLEANED);
+        let result = check(RAW, &output, true, false);
+        assert_eq!(result.path, RefinePath::Refined);
+        assert_eq!(result.text, CLEANED);
+        assert_eq!(result.reason.as_deref(), Some("Removed preamble"));
+
+        assert_eq!(
+            strip_preamble("以下が修正後のテキストです：\n会議は木曜日です。"),
+            "会議は木曜日です。"
+        );
+    }
+
+    #[test]
+    fn code_fence_is_removed_with_its_language_tag() {
+        assert_eq!(strip_preamble("```text\nHello there.\n```"), "Hello there.");
+        assert_eq!(
+            strip_preamble("Sure! Corrected version:\n```\nHello there.\n```"),
+            "Hello there."
+        );
+    }
+
+    #[test]
+    fn first_line_is_kept_unless_it_is_a_preamble() {
+        // Ends with a colon but has no marker: part of the text.
+        assert_eq!(strip_preamble("Agenda:\nbudget"), "Agenda:\nbudget");
+        // Has a marker but no colon.
+        assert_eq!(strip_preamble("Sure thing\nok"), "Sure thing\nok");
+        // Too long to be an opener.
+        let long = format!("Here is {}:\nbody", "a very long line ".repeat(6));
+        assert_eq!(strip_preamble(&long), long);
+    }
+
+    #[test]
+    fn empty_output_falls_back() {
+        let result = check(RAW, "Here is the cleaned text:\n  ", true, false);
+        assert_eq!(result.path, RefinePath::Fallback);
+        assert_eq!(result.text, RAW);
+    }
+
+    #[test]
+    fn output_of_the_wrong_length_falls_back() {
+        let short = check(RAW, "Thursday.", true, false);
+        assert_eq!(short.path, RefinePath::Fallback);
+        assert!(short.reason.unwrap().starts_with("Output length"));
+
+        let long = check(RAW, &format!("{} {}", RAW, RAW.repeat(2)), true, false);
+        assert_eq!(long.path, RefinePath::Fallback);
+    }
+
+    #[test]
+    fn unrelated_output_falls_back() {
+        let answer = "The meeting has been rescheduled; invitations were sent to everyone.";
+        let result = check(RAW, answer, true, false);
+        assert_eq!(result.path, RefinePath::Fallback);
+        assert!(
+            result
+                .reason
+                .unwrap()
+                .starts_with("Output is too different")
+        );
+    }
+
+    #[test]
+    fn divergence_check_can_be_disabled() {
+        let result = check(RAW, "Thursday.", false, false);
+        assert_eq!(result.path, RefinePath::Refined);
+    }
+
+    #[test]
+    fn short_transcripts_are_not_checked() {
+        let result = check("um yes", "Yes, definitely, let's do that.", true, false);
+        assert_eq!(result.path, RefinePath::Refined);
+    }
+
+    #[test]
+    fn stopped_output_skips_the_length_check() {
+        let partial = "I think we should move";
+        assert_eq!(check(RAW, partial, true, false).path, RefinePath::Fallback);
+        let result = check(RAW, partial, true, true);
+        assert_eq!(result.path, RefinePath::Refined);
+        assert_eq!(result.text, partial);
+    }
+
+    #[test]
+    fn stopped_output_is_still_compared_with_the_transcript() {
+        let result = check(RAW, "Dear team, please note", true, true);
+        assert_eq!(result.path, RefinePath::Fallback);
+    }
+
+    #[test]
+    fn similarity_ignores_case_punctuation_and_spacing() {
+        assert_eq!(similarity("Hello, world!", "hello world"), 1.0);
+        assert_eq!(similarity("会議は木曜日", "会議は、木曜日。"), 1.0);
+        assert_eq!(similarity("", "!!"), 1.0);
+        assert_eq!(similarity("abc", ""), 0.0);
+        assert_eq!(similarity("abcd", "wxyz"), 0.0);
+    }
+
+    #[test]
+    fn prefix_counts_characters() {
+        assert_eq!(prefix("会議は木曜日", 3), "会議は");
+        assert_eq!(prefix("abc", 10), "abc");
+    }
+}
@@ -0,0 +1,127 @@
+use super::{ChatMessage, GenerationOptions, RefinePrompt, RefinementProvider};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::time::Duration;
+
+pub struct OpenAiProvider {
+    base_url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelEntry {
+    id: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    stop: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+}
+
+#[derive(Serialize, Debug)]
+struct ResponseFormat {
+    r#type: &'static str,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatChoice {
+    message: ChatMessage,
+}
+
+impl OpenAiProvider {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Self {
+        // Accept both "http://host:8080" and "http://host:8080/v1".
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v1").unwrap_or(base_url);
+        Self {
+            base_url: base_url.to_string(),
+            api_key,
+        }
+    }
+
+    fn authorize(&self, req: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => req.bearer_auth(key),
+            None => req,
+        }
+    }
+}
+
+#[async_trait]
+impl RefinementProvider for OpenAiProvider {
+    async fn list_models(&self) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        let url = format!("{}/v1/models", self.base_url);
+        let res = self
+            .authorize(client.get(&url))
+            .send()
+            .await?
+            .error_for_status()?;
+        let models: ModelsResponse = res.json().await?;
+
+        Ok(models.data.into_iter().map(|m| m.id).collect())
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &RefinePrompt,
+        options: &GenerationOptions,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(120))
+            .build()?;
+        // num_ctx and keep_alive are Ollama-specific and fixed at server start here.
+        let req = ChatCompletionRequest {
+            model,
+            messages: prompt.to_messages(),
+            stream: false,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            seed: options.seed,
+            stop: &options.stop,
+            response_format: options.json.then_some(ResponseFormat {
+                r#type: "json_object",
+            }),
+        };
+
+        let url = format!("{}/v1/chat/completions", self.base_url);
+        let res = self
+            .authorize(client.post(&url).json(&req))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let completion: ChatCompletionResponse = res.json().await?;
+        let choice = completion
+            .choices
+            .into_iter()
+            .next()
+            .ok_or("Empty response from chat-completions server")?;
+        Ok(choice.message.content)
+    }
+}
@@ -0,0 +1,316 @@
+mod openai;
+mod whisper;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+pub use openai::OpenAiEngine;
+pub use whisper::WhisperEngine;
+
+/// Options applied to a single transcription run.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TranscribeOptions {
+    pub language: Option<String>,
+    #[serde(default)]
+    pub translate: bool,
+    pub initial_prompt: Option<String>,
+}
+
+/// What a backend can do, so the UI can enable or hide options accordingly.
+#[derive(Clone, Debug, Serialize)]
+pub struct EngineCapabilities {
+    pub name: String,
+    pub multilingual: bool,
+    pub translate: bool,
+    pub gpu: bool,
+    pub remote: bool,
+}
+
+/// A speech-recognition backend.
+pub trait SpeechEngine: Send {
+    /// Prepare the engine (load weights, check the server). Called once before `transcribe`.
+    fn load(&mut self) -> Result<()>;
+    /// Transcribe 16 kHz mono samples.
+    fn transcribe(&mut self, samples: &[f32], options: &TranscribeOptions) -> Result<String>;
+    fn capabilities(&self) -> EngineCapabilities;
+}
+
+/// Backend selected in the settings UI.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SpeechBackend {
+    #[default]
+    Whisper,
+    OpenAi {
+        base_url: String,
+        model: String,
+        api_key: Option<String>,
+    },
+}
+
+/// Everything needed to build an engine. A loaded engine is reused while this stays equal.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EngineConfig {
+    Whisper {
+        model_path: String,
+        use_gpu: bool,
+    },
+    OpenAi {
+        base_url: String,
+        model: String,
+        api_key: Option<String>,
+    },
+}
+
+impl EngineConfig {
+    pub fn new(backend: SpeechBackend, model_path: Option<String>, use_gpu: bool) -> Result<Self> {
+        Ok(match backend {
+            SpeechBackend::Whisper => EngineConfig::Whisper {
+                model_path: model_path.ok_or_else(|| anyhow::anyhow!("No model selected"))?,
+                use_gpu,
+            },
+            SpeechBackend::OpenAi {
+                base_url,
+                model,
+                api_key,
+            } => EngineConfig::OpenAi {
+                base_url,
+                model,
+                api_key: api_key.filter(|k| !k.is_empty()),
+            },
+        })
+    }
+
+    fn build(&self) -> Box<dyn SpeechEngine> {
+        match self {
+            EngineConfig::Whisper {
+                model_path,
+                use_gpu,
+            } => Box::new(WhisperEngine::new(model_path, *use_gpu)),
+            EngineConfig::OpenAi {
+                base_url,
+                model,
+                api_key,
+            } => Box::new(OpenAiEngine::new(base_url, model, api_key.clone())),
+        }
+    }
+}
+
+struct LoadedEngine {
+    config: EngineConfig,
+    engine: Box<dyn SpeechEngine>,
+}
+
+/// Builds an engine for a config; `EngineConfig::build` outside tests.
+type BuildEngine = dyn Fn(&EngineConfig) -> Box<dyn SpeechEngine> + Send + Sync;
+
+/// Holds the most recently used engine so the model isn't reloaded for every dictation.
+pub struct SpeechState {
+    loaded: Arc<Mutex<Option<LoadedEngine>>>,
+    /// Kept apart from `loaded`, which is held for a whole load and transcription.
+    capabilities: Arc<Mutex<Option<EngineCapabilities>>>,
+    build: Arc<BuildEngine>,
+}
+
+impl SpeechState {
+    pub fn new() -> Self {
+        Self::with_builder(EngineConfig::build)
+    }
+
+    /// A state whose engines come from `build` instead of the real backends.
+    pub fn with_builder(
+        build: impl Fn(&EngineConfig) -> Box<dyn SpeechEngine> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            loaded: Arc::new(Mutex::new(None)),
+            capabilities: Arc::new(Mutex::new(None)),
+            build: Arc::new(build),
+        }
+    }
+
+    /// Run `f` with an engine for `config`, building and loading a new one if needed.
+    /// This blocks while the model loads or the server responds.
+    pub fn with_engine<T>(
+        &self,
+        config: EngineConfig,
+        f: impl FnOnce(&mut dyn SpeechEngine) -> Result<T>,
+    ) -> Result<T> {
+        let mut loaded = self.loaded.lock().unwrap_or_else(|poisoned| {
+            // A panic in the engine left it in an unknown state: start over with a new one.
+            tracing::warn!("speech engine panicked earlier, reloading it");
+            self.loaded.clear_poison();
+            let mut loaded = poisoned.into_inner();
+            *loaded = None;
+            self.set_capabilities(None);
+            loaded
+        });
+        if loaded.as_ref().is_none_or(|l| l.config != config) {
+            // Drop the previous engine first so two models are never resident at once.
+            *loaded = None;
+            self.set_capabilities(None);
+            let mut engine = (self.build)(&config);
+            engine.load()?;
+            self.set_capabilities(Some(engine.capabilities()));
+            *loaded = Some(LoadedEngine { config, engine });
+        }
+        let loaded = loaded.as_mut().expect("engine was just loaded");
+        f(loaded.engine.as_mut())
+    }
+
+    /// Capabilities of the loaded engine. Doesn't wait for a running transcription.
+    pub fn capabilities(&self) -> Option<EngineCapabilities> {
+        self.capabilities.lock().ok()?.clone()
+    }
+
+    fn set_capabilities(&self, capabilities: Option<EngineCapabilities>) {
+        if let Ok(mut current) = self.capabilities.lock() {
+            *current = capabilities;
+        }
+    }
+
+    pub fn handle(&self) -> Self {
+        Self {
+            loaded: self.loaded.clone(),
+            capabilities: self.capabilities.clone(),
+            build: self.build.clone(),
+        }
+    }
+}
+
+impl Default for SpeechState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Echoes its model path, counting how often it was loaded.
+    struct FakeEngine {
+        name: String,
+        loads: Arc<AtomicUsize>,
+        fail: bool,
+    }
+
+    impl SpeechEngine for FakeEngine {
+        fn load(&mut self) -> Result<()> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                anyhow::bail!("model file is corrupt");
+            }
+            Ok(())
+        }
+
+        fn transcribe(&mut self, samples: &[f32], _options: &TranscribeOptions) -> Result<String> {
+            Ok(format!("{}: {} samples", self.name, samples.len()))
+        }
+
+        fn capabilities(&self) -> EngineCapabilities {
+            EngineCapabilities {
+                name: self.name.clone(),
+                multilingual: true,
+                translate: false,
+                gpu: false,
+                remote: false,
+            }
+        }
+    }
+
+    /// A state building fake engines, and its load counter. Models named "broken" fail.
+    fn fake_state() -> (SpeechState, Arc<AtomicUsize>) {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let counter = loads.clone();
+        let state = SpeechState::with_builder(move |config| {
+            let name = match config {
+                EngineConfig::Whisper { model_path, .. } => model_path.clone(),
+                EngineConfig::OpenAi { model, .. } => model.clone(),
+            };
+            Box::new(FakeEngine {
+                fail: name == "broken",
+                name,
+                loads: counter.clone(),
+            })
+        });
+        (state, loads)
+    }
+
+    fn whisper(model_path: &str) -> EngineConfig {
+        EngineConfig::Whisper {
+            model_path: model_path.to_string(),
+            use_gpu: false,
+        }
+    }
+
+    fn transcribe(state: &SpeechState, config: EngineConfig) -> Result<String> {
+        state.with_engine(config, |engine| {
+            engine.transcribe(&[0.0; 160], &TranscribeOptions::default())
+        })
+    }
+
+    #[test]
+    fn engine_is_reused_while_the_config_is_unchanged() {
+        let (state, loads) = fake_state();
+        assert_eq!(
+            transcribe(&state, whisper("base")).unwrap(),
+            "base: 160 samples"
+        );
+        assert_eq!(
+            transcribe(&state, whisper("base")).unwrap(),
+            "base: 160 samples"
+        );
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            transcribe(&state, whisper("small")).unwrap(),
+            "small: 160 samples"
+        );
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert_eq!(state.capabilities().unwrap().name, "small");
+    }
+
+    #[test]
+    fn capabilities_are_readable_during_a_transcription() {
+        let (state, _) = fake_state();
+        let handle = state.handle();
+        let name = state
+            .with_engine(whisper("base"), |_| {
+                Ok(handle.capabilities().map(|c| c.name))
+            })
+            .unwrap();
+        assert_eq!(name.as_deref(), Some("base"));
+    }
+
+    #[test]
+    fn panicking_engine_is_replaced() {
+        let (state, loads) = fake_state();
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            state.with_engine(whisper("base"), |_| -> Result<()> {
+                panic!("whisper aborted")
+            })
+        }));
+        assert!(panicked.is_err());
+
+        assert_eq!(
+            transcribe(&state, whisper("base")).unwrap(),
+            "base: 160 samples"
+        );
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+        assert_eq!(state.capabilities().unwrap().name, "base");
+    }
+
+    #[test]
+    fn failed_load_leaves_no_engine() {
+        let (state, loads) = fake_state();
+        transcribe(&state, whisper("base")).unwrap();
+        assert!(transcribe(&state, whisper("broken")).is_err());
+        assert!(state.capabilities().is_none());
+
+        // The failed engine isn't kept, so the next attempt loads again.
+        assert!(transcribe(&state, whisper("broken")).is_err());
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,119 @@
+use super::{EngineCapabilities, SpeechEngine, TranscribeOptions};
+use anyhow::{Context, Result, bail};
+use reqwest::blocking::{Client, multipart};
+use serde::Deserialize;
+use std::io::Cursor;
+use std::time::Duration;
+
+/// Transcription through an OpenAI-compatible `/v1/audio/transcriptions` server
+/// (faster-whisper-server, whisper.cpp server, ...).
+pub struct OpenAiEngine {
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    client: Option<Client>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+impl OpenAiEngine {
+    pub fn new(base_url: &str, model: &str, api_key: Option<String>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key,
+            client: None,
+        }
+    }
+
+    fn endpoint(&self, translate: bool) -> String {
+        let path = if translate {
+            "translations"
+        } else {
+            "transcriptions"
+        };
+        format!("{}/v1/audio/{}", self.base_url, path)
+    }
+}
+
+impl SpeechEngine for OpenAiEngine {
+    fn load(&mut self) -> Result<()> {
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(300))
+            .build()?;
+        // Not every server implements /v1/models, so any HTTP response counts as reachable.
+        client
+            .get(format!("{}/v1/models", self.base_url))
+            .timeout(Duration::from_secs(10))
+            .send()
+            .with_context(|| format!("Transcription server unreachable at {}", self.base_url))?;
+        self.client = Some(client);
+        Ok(())
+    }
+
+    fn transcribe(&mut self, samples: &[f32], options: &TranscribeOptions) -> Result<String> {
+        let client = self.client.as_ref().context("Engine not loaded")?;
+
+        let file = multipart::Part::bytes(encode_wav(samples)?)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")?;
+        let mut form = multipart::Form::new()
+            .text("model", self.model.clone())
+            .text("response_format", "json")
+            .part("file", file);
+        if let Some(language) = options.language.clone().filter(|_| !options.translate) {
+            form = form.text("language", language);
+        }
+        if let Some(prompt) = options.initial_prompt.clone() {
+            form = form.text("prompt", prompt);
+        }
+
+        let mut req = client
+            .post(self.endpoint(options.translate))
+            .multipart(form);
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        }
+        let res = req.send()?;
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().unwrap_or_default();
+            bail!("Transcription server returned {}: {}", status, body.trim());
+        }
+        let body: TranscriptionResponse = res
+            .json()
+            .context("Unexpected response from transcription server")?;
+        Ok(body.text)
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: format!("openai:{}", self.model),
+            multilingual: true,
+            translate: true,
+            gpu: false,
+            remote: true,
+        }
+    }
+}
+
+/// Encode 16 kHz mono samples as a 16-bit PCM WAV file, which every server accepts.
+fn encode_wav(samples: &[f32]) -> Result<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 16000,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut cursor = Cursor::new(Vec::with_capacity(samples.len() * 2 + 44));
+    let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(cursor.into_inner())
+}
@@ -0,0 +1,81 @@
+use super::{EngineCapabilities, SpeechEngine, TranscribeOptions};
+use crate::ggml;
+use anyhow::{Result, anyhow};
+
+/// Local inference with whisper.cpp through `whisper_rs`.
+pub struct WhisperEngine {
+    model_path: String,
+    use_gpu: bool,
+    multilingual: bool,
+    ctx: Option<whisper_rs::WhisperContext>,
+}
+
+impl WhisperEngine {
+    pub fn new(model_path: &str, use_gpu: bool) -> Self {
+        Self {
+            model_path: model_path.to_string(),
+            use_gpu,
+            multilingual: true,
+            ctx: None,
+        }
+    }
+}
+
+impl SpeechEngine for WhisperEngine {
+    fn load(&mut self) -> Result<()> {
+        let info = ggml::read_model_info(&self.model_path)?;
+        self.multilingual = info.multilingual;
+
+        let mut params = whisper_rs::WhisperContextParameters::default();
+        params.use_gpu(self.use_gpu);
+
+        let ctx = whisper_rs::WhisperContext::new_with_params(&self.model_path, params)
+            .map_err(|e| anyhow!("Failed to load model: {}", e))?;
+        self.ctx = Some(ctx);
+        Ok(())
+    }
+
+    fn transcribe(&mut self, samples: &[f32], options: &TranscribeOptions) -> Result<String> {
+        let ctx = self
+            .ctx
+            .as_ref()
+            .ok_or_else(|| anyhow!("Model not loaded"))?;
+        let mut state = ctx.create_state().map_err(|e| anyhow!(e.to_string()))?;
+
+        let mut params =
+            whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 1 });
+
+        // English-only models only understand "en"; anything else produces garbage.
+        let lang = if self.multilingual {
+            options.language.as_deref().unwrap_or("en")
+        } else {
+            "en"
+        };
+        params.set_language(Some(lang));
+        params.set_translate(options.translate && self.multilingual);
+        if let Some(prompt) = options.initial_prompt.as_deref() {
+            params.set_initial_prompt(prompt);
+        }
+
+        state
+            .full(params, samples)
+            .map_err(|e| anyhow!("Transcription failed: {}", e))?;
+
+        let mut text = String::new();
+        for segment in state.as_iter() {
+            let segment_text = segment.to_str_lossy().map_err(|e| anyhow!(e.to_string()))?;
+            text.push_str(segment_text.as_ref());
+        }
+        Ok(text)
+    }
+
+    fn capabilities(&self) -> EngineCapabilities {
+        EngineCapabilities {
+            name: "whisper".to_string(),
+            multilingual: self.multilingual,
+            translate: self.multilingual,
+            gpu: self.use_gpu,
+            remote: false,
+        }
+    }
+}
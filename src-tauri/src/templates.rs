@@ -0,0 +1,585 @@
+use crate::clipboard;
+use crate::error::AppError;
+use crate::refine::{FewShotExample, GenerationOptions, RefinePrompt};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+/// Built-in template, used when no other is active.
+const DEFAULT_ID: &str = "default";
+const DEFAULT_PROMPT: &str = "以下の文章の『えー』『あの』などのフィラーを取り除き、句読点を適切に補って、自然な日本語の文章に修正してください。出力は修正後の文章のみにしてください。\n\n対象の文章: {text}";
+
+/// A named refinement prompt. Variables in `prompt` and `system_prompt` are filled in
+/// by [`TemplateVars::render`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub id: String,
+    pub name: String,
+    /// Single-prompt template, used when `system_prompt` is empty.
+    #[serde(default)]
+    pub prompt: String,
+    /// Switches to the chat API when set.
+    #[serde(default)]
+    pub system_prompt: String,
+    #[serde(default)]
+    pub examples: Vec<FewShotExample>,
+    /// Global shortcut that starts a dictation with this template, e.g. "Ctrl+Alt+1".
+    #[serde(default)]
+    pub shortcut: Option<String>,
+    #[serde(default)]
+    pub options: GenerationOptions,
+    /// Fall back to the raw transcript when the output differs too much from it.
+    /// Turn off for templates that translate or summarize.
+    #[serde(default = "default_true")]
+    pub check_divergence: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl PromptTemplate {
+    pub fn to_prompt(&self, vars: &TemplateVars) -> RefinePrompt {
+        if self.system_prompt.trim().is_empty() {
+            RefinePrompt::Completion(vars.render(&self.prompt))
+        } else {
+            RefinePrompt::chat(&vars.render(&self.system_prompt), &self.examples, vars.text)
+        }
+    }
+}
+
+/// Values available to templates besides `{text}`.
+pub struct TemplateVars<'a> {
+    pub text: &'a str,
+    pub language: &'a str,
+    pub app_name: &'a str,
+    pub previous_transcript: &'a str,
+}
+
+impl TemplateVars<'_> {
+    /// Fill in the variables in one pass, so values containing `{...}`, such as the
+    /// dictated text or the clipboard, are never substituted again.
+    pub fn render(&self, template: &str) -> String {
+        substitute(template, |name| {
+            Some(match name {
+                "text" => self.text.to_string(),
+                "language" => self.language.to_string(),
+                "date" => chrono::Local::now().format("%Y-%m-%d").to_string(),
+                "app_name" => self.app_name.to_string(),
+                "previous_transcript" => self.previous_transcript.to_string(),
+                // Only touch the clipboard when the template asks for it.
+                "clipboard" => clipboard::read_text().unwrap_or_default(),
+                _ => return None,
+            })
+        })
+    }
+}
+
+/// Replace each `{name}` in `template` with `lookup(name)`. Unknown names and unmatched
+/// braces are kept as written.
+fn substitute(template: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let value = tail
+            .find('}')
+            .and_then(|end| lookup(&tail[1..end]).map(|value| (value, end)));
+        match value {
+            Some((value, end)) => {
+                out.push_str(&value);
+                rest = &tail[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct TemplateFile {
+    active: Option<String>,
+    templates: Vec<PromptTemplate>,
+}
+
+impl TemplateFile {
+    fn builtin() -> Self {
+        Self {
+            active: Some(DEFAULT_ID.to_string()),
+            templates: vec![PromptTemplate {
+                id: DEFAULT_ID.to_string(),
+                name: "Default".to_string(),
+                prompt: DEFAULT_PROMPT.to_string(),
+                system_prompt: String::new(),
+                examples: Vec::new(),
+                shortcut: None,
+                options: GenerationOptions::default(),
+                check_divergence: true,
+            }],
+        }
+    }
+}
+
+/// The saved templates, or `None` when there is no file yet.
+fn read_file(path: &Path) -> Result<Option<TemplateFile>, String> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Move an unreadable templates file out of the way, and say what happened.
+fn set_aside(path: &Path, error: &str) -> String {
+    let backup = backup_path(path);
+    match fs::rename(path, &backup) {
+        Ok(()) => format!(
+            "Saved templates couldn't be loaded ({}). The file was kept as {} and the \
+             default template is used instead.",
+            error,
+            backup.display()
+        ),
+        Err(e) => {
+            tracing::error!(path = %path.display(), "failed to back up templates: {}", e);
+            format!(
+                "Saved templates couldn't be loaded ({}) and {} was left in place; template \
+                 changes won't be saved until it is fixed or removed.",
+                error,
+                path.display()
+            )
+        }
+    }
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Templates persisted as JSON in the app config dir.
+pub struct TemplateStore {
+    path: PathBuf,
+    data: Mutex<TemplateFile>,
+    /// Why the file couldn't be loaded, shown in the template settings.
+    load_error: Option<String>,
+    registered_shortcuts: Mutex<Vec<(Shortcut, String)>>,
+    /// Transcript of the previous dictation, for `{previous_transcript}`.
+    pub previous_transcript: Mutex<String>,
+    /// Application that had focus when the shortcut was pressed, for `{app_name}`.
+    pub app_name: Mutex<String>,
+}
+
+impl TemplateStore {
+    /// Templates from `path`, or the built-in set when it doesn't exist yet. A file that
+    /// can't be read is moved to `templates.json.bak` so that saving doesn't destroy it.
+    pub fn load(path: PathBuf) -> Self {
+        let (data, load_error) = match read_file(&path) {
+            Ok(Some(data)) => (data, None),
+            Ok(None) => (TemplateFile::builtin(), None),
+            Err(e) => {
+                tracing::error!(path = %path.display(), "failed to load templates: {}", e);
+                (TemplateFile::builtin(), Some(set_aside(&path, &e)))
+            }
+        };
+        Self {
+            path,
+            data: Mutex::new(data),
+            load_error,
+            registered_shortcuts: Mutex::new(Vec::new()),
+            previous_transcript: Mutex::new(String::new()),
+            app_name: Mutex::new(String::new()),
+        }
+    }
+
+    /// Why the saved templates couldn't be loaded, if they couldn't.
+    pub fn load_error(&self) -> Option<String> {
+        self.load_error.clone()
+    }
+
+    pub fn list(&self) -> Vec<PromptTemplate> {
+        self.data
+            .lock()
+            .map(|d| d.templates.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn active_id(&self) -> Option<String> {
+        self.data.lock().ok().and_then(|d| d.active.clone())
+    }
+
+    /// Template by id, falling back to the active template.
+    pub fn resolve(&self, id: Option<&str>) -> Option<PromptTemplate> {
+        let data = self.data.lock().ok()?;
+        let id = id.or(data.active.as_deref())?;
+        data.templates.iter().find(|t| t.id == id).cloned()
+    }
+
+    pub fn set_active(&self, id: Option<String>) -> Result<(), AppError> {
+        let mut data = self.data.lock()?;
+        if let Some(id) = &id
+            && !data.templates.iter().any(|t| &t.id == id)
+        {
+            return Err(AppError::TemplateNotFound(id.clone()));
+        }
+        data.active = id;
+        self.save(&data)
+    }
+
+    /// Insert or replace templates by id.
+    pub fn upsert(&self, templates: Vec<PromptTemplate>) -> Result<(), AppError> {
+        let mut data = self.data.lock()?;
+        for mut template in templates {
+            if template.id.is_empty() {
+                template.id = new_id();
+            }
+            match data.templates.iter_mut().find(|t| t.id == template.id) {
+                Some(existing) => *existing = template,
+                None => data.templates.push(template),
+            }
+        }
+        self.save(&data)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), AppError> {
+        let mut data = self.data.lock()?;
+        data.templates.retain(|t| t.id != id);
+        if data.active.as_deref() == Some(id) {
+            // The default template, or any other if that was deleted too.
+            data.active = data
+                .templates
+                .iter()
+                .find(|t| t.id == DEFAULT_ID)
+                .or(data.templates.first())
+                .map(|t| t.id.clone());
+        }
+        self.save(&data)
+    }
+
+    pub fn template_for_shortcut(&self, shortcut: &Shortcut) -> Option<String> {
+        let registered = self.registered_shortcuts.lock().ok()?;
+        registered
+            .iter()
+            .find(|(s, _)| s.id() == shortcut.id())
+            .map(|(_, id)| id.clone())
+    }
+
+    fn save(&self, data: &TemplateFile) -> Result<(), AppError> {
+        // The unreadable file is still there if it couldn't be moved aside.
+        if self.path.exists() && read_file(&self.path).is_err() {
+            return Err(AppError::InvalidTemplateFile(format!(
+                "{} can't be read and wasn't backed up, so it was not overwritten",
+                self.path.display()
+            )));
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(data).map_err(AppError::internal)?;
+        Ok(fs::write(&self.path, json)?)
+    }
+}
+
+/// Register the per-template global shortcuts, replacing the previous set.
+pub fn register_shortcuts(app: &AppHandle) -> Result<(), AppError> {
+    let store = app.state::<TemplateStore>();
+    let mut registered = store.registered_shortcuts.lock()?;
+
+    for (shortcut, _) in registered.drain(..) {
+        let _ = app.global_shortcut().unregister(shortcut);
+    }
+
+    let mut errors = Vec::new();
+    for template in store.list() {
+        let Some(accelerator) = template.shortcut.as_deref().filter(|s| !s.is_empty()) else {
+            continue;
+        };
+        match accelerator.parse::<Shortcut>() {
+            Ok(shortcut) => match app.global_shortcut().register(shortcut) {
+                Ok(()) => registered.push((shortcut, template.id)),
+                Err(e) => errors.push(format!("{}: {}", accelerator, e)),
+            },
+            Err(e) => errors.push(format!("{}: {}", accelerator, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Shortcut(errors.join(", ")))
+    }
+}
+
+/// Read templates from a file written by [`export_to`], a bare list, or a single template.
+pub fn import_from(path: &Path) -> Result<Vec<PromptTemplate>, AppError> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ImportFile {
+        File(TemplateFile),
+        List(Vec<PromptTemplate>),
+        Single(PromptTemplate),
+    }
+
+    let json = fs::read_to_string(path)?;
+    let parsed: ImportFile =
+        serde_json::from_str(&json).map_err(|e| AppError::InvalidTemplateFile(e.to_string()))?;
+    Ok(match parsed {
+        ImportFile::File(file) => file.templates,
+        ImportFile::List(list) => list,
+        ImportFile::Single(template) => vec![template],
+    })
+}
+
+pub fn export_to(path: &Path, templates: Vec<PromptTemplate>) -> Result<(), AppError> {
+    let file = TemplateFile {
+        active: None,
+        templates,
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(AppError::internal)?;
+    Ok(fs::write(path, json)?)
+}
+
+#[derive(Serialize)]
+pub struct TemplateList {
+    active: Option<String>,
+    templates: Vec<PromptTemplate>,
+    /// Why the saved templates couldn't be loaded at startup.
+    load_error: Option<String>,
+}
+
+fn template_list(store: &TemplateStore) -> TemplateList {
+    TemplateList {
+        active: store.active_id(),
+        templates: store.list(),
+        load_error: store.load_error(),
+    }
+}
+
+/// Re-register shortcuts and rebuild the tray menu after templates changed.
+fn templates_changed(app: &AppHandle) -> Result<(), AppError> {
+    crate::tray::refresh_menu(app)?;
+    register_shortcuts(app)
+}
+
+#[tauri::command]
+pub fn list_templates(store: State<TemplateStore>) -> TemplateList {
+    template_list(&store)
+}
+
+#[tauri::command]
+pub fn save_template(
+    app: AppHandle,
+    store: State<TemplateStore>,
+    template: PromptTemplate,
+) -> Result<TemplateList, AppError> {
+    store.upsert(vec![template])?;
+    templates_changed(&app)?;
+    Ok(template_list(&store))
+}
+
+#[tauri::command]
+pub fn delete_template(
+    app: AppHandle,
+    store: State<TemplateStore>,
+    id: String,
+) -> Result<TemplateList, AppError> {
+    store.delete(&id)?;
+    templates_changed(&app)?;
+    Ok(template_list(&store))
+}
+
+#[tauri::command]
+pub fn set_active_template(
+    app: AppHandle,
+    store: State<TemplateStore>,
+    id: Option<String>,
+) -> Result<TemplateList, AppError> {
+    store.set_active(id)?;
+    crate::tray::refresh_menu(&app)?;
+    Ok(template_list(&store))
+}
+
+#[tauri::command]
+pub async fn import_templates(
+    app: AppHandle,
+    store: State<'_, TemplateStore>,
+) -> Result<TemplateList, AppError> {
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter("Template", &["json"])
+        .blocking_pick_file()
+        .ok_or(AppError::NoFileSelected)?;
+    let path = file_path
+        .as_path()
+        .ok_or_else(|| AppError::Io("Invalid path".to_string()))?;
+
+    store.upsert(import_from(path)?)?;
+    templates_changed(&app)?;
+    Ok(template_list(&store))
+}
+
+#[tauri::command]
+pub async fn export_templates(
+    app: AppHandle,
+    store: State<'_, TemplateStore>,
+) -> Result<String, AppError> {
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter("Template", &["json"])
+        .set_file_name("opensw-templates.json")
+        .blocking_save_file()
+        .ok_or(AppError::NoFileSelected)?;
+    let path = file_path
+        .as_path()
+        .ok_or_else(|| AppError::Io("Invalid path".to_string()))?;
+
+    export_to(path, store.list())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn new_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("{:x}", nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars<'a>(text: &'a str) -> TemplateVars<'a> {
+        TemplateVars {
+            text,
+            language: "ja",
+            app_name: "Mail",
+            previous_transcript: "earlier",
+        }
+    }
+
+    #[test]
+    fn variables_are_filled_in() {
+        let rendered =
+            vars("hello").render("{app_name} ({language}), after '{previous_transcript}': {text}");
+        assert_eq!(rendered, "Mail (ja), after 'earlier': hello");
+    }
+
+    #[test]
+    fn dictated_braces_are_left_alone() {
+        let rendered = vars("say {language} and {app_name}").render("{text} / {language}");
+        assert_eq!(rendered, "say {language} and {app_name} / ja");
+    }
+
+    #[test]
+    fn unknown_and_unmatched_braces_are_kept() {
+        let lookup = |name: &str| (name == "x").then(|| "1".to_string());
+        assert_eq!(substitute("{y} {x} {", lookup), "{y} 1 {");
+        assert_eq!(substitute("{{x}}", lookup), "{1}");
+        assert_eq!(substitute("json: {\"a\": {x}}", lookup), "json: {\"a\": 1}");
+    }
+
+    #[test]
+    fn lookup_runs_only_for_placeholders_present() {
+        let mut looked_up = Vec::new();
+        substitute("{text}", |name| {
+            looked_up.push(name.to_string());
+            Some(String::new())
+        });
+        assert_eq!(looked_up, ["text"]);
+    }
+
+    /// A store backed by a fresh file in the temp dir, starting with the default template.
+    fn store(name: &str) -> TemplateStore {
+        let path = std::env::temp_dir().join(format!(
+            "opensw-templates-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        TemplateStore::load(path)
+    }
+
+    fn template(id: &str) -> PromptTemplate {
+        PromptTemplate {
+            id: id.to_string(),
+            name: id.to_string(),
+            prompt: "{text}".to_string(),
+            system_prompt: String::new(),
+            examples: Vec::new(),
+            shortcut: None,
+            options: GenerationOptions::default(),
+            check_divergence: true,
+        }
+    }
+
+    #[test]
+    fn corrupt_file_is_kept_when_saving() {
+        let store = store("corrupt");
+        let corrupt = b"{\"active\": \"mail\", \"templates\": [{\"id\": \"ma";
+        fs::write(&store.path, corrupt).unwrap();
+
+        let store = TemplateStore::load(store.path.clone());
+        assert!(store.load_error().unwrap().contains(".bak"));
+        assert_eq!(store.active_id().as_deref(), Some(DEFAULT_ID));
+        store.upsert(vec![template("mail")]).unwrap();
+
+        let backup = backup_path(&store.path);
+        assert_eq!(fs::read(&backup).unwrap(), corrupt);
+        assert_eq!(TemplateStore::load(store.path.clone()).list().len(), 2);
+        let _ = fs::remove_file(&store.path);
+        let _ = fs::remove_file(&backup);
+    }
+
+    #[test]
+    fn missing_file_gives_the_default_template() {
+        let store = store("missing");
+        assert_eq!(store.load_error(), None);
+        assert_eq!(store.active_id().as_deref(), Some(DEFAULT_ID));
+        assert_eq!(store.list().len(), 1);
+    }
+
+    #[test]
+    fn deleting_the_active_template_falls_back_to_the_default() {
+        let store = store("delete-active");
+        store
+            .upsert(vec![template("mail"), template("notes")])
+            .unwrap();
+        store.set_active(Some("notes".to_string())).unwrap();
+        store.delete("notes").unwrap();
+        assert_eq!(store.active_id().as_deref(), Some(DEFAULT_ID));
+
+        // Without the default, another template takes over.
+        store.set_active(Some(DEFAULT_ID.to_string())).unwrap();
+        store.delete(DEFAULT_ID).unwrap();
+        assert_eq!(store.active_id().as_deref(), Some("mail"));
+
+        store.delete("mail").unwrap();
+        assert_eq!(store.active_id(), None);
+        let _ = fs::remove_file(&store.path);
+    }
+
+    #[test]
+    fn deleting_another_template_keeps_the_active_one() {
+        let store = store("delete-other");
+        store.upsert(vec![template("mail")]).unwrap();
+        store.set_active(Some("mail".to_string())).unwrap();
+        store.delete(DEFAULT_ID).unwrap();
+        assert_eq!(store.active_id().as_deref(), Some("mail"));
+        let _ = fs::remove_file(&store.path);
+    }
+}
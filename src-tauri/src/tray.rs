@@ -1,14 +1,20 @@
+use crate::audio::{AudioState, MicStatus};
+use crate::templates::TemplateStore;
 use tauri::{
-    Manager,
-    menu::{Menu, MenuItem},
+    AppHandle, Emitter, Manager, Runtime,
+    image::Image,
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
 };
 
-pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
-    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-    let show_item = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
+const TRAY_ID: &str = "main";
+const TEMPLATE_PREFIX: &str = "template:";
+/// Recording dot, as a fraction of the icon's size.
+const RECORDING_DOT_RADIUS: f32 = 0.3;
+const RECORDING_RED: [u8; 4] = [229, 57, 53, 255];
 
-    let menu = Menu::with_items(app, &[&show_item, &quit_item])?;
+pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+    let menu = build_menu(app)?;
 
     // Use default_window_icon if available
     let icon = app
@@ -16,7 +22,7 @@ pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
         .expect("No default window icon found")
         .clone();
 
-    let _tray = TrayIconBuilder::new()
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
         .icon(icon)
         .menu(&menu)
         .show_menu_on_left_click(false)
@@ -30,6 +36,12 @@ pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
                     let _ = window.set_focus();
                 }
             }
+            id if id.starts_with(TEMPLATE_PREFIX) => {
+                let template_id = id.trim_start_matches(TEMPLATE_PREFIX).to_string();
+                let _ = app.state::<TemplateStore>().set_active(Some(template_id));
+                let _ = refresh_menu(app);
+                let _ = app.emit("templates-changed", ());
+            }
             _ => {}
         })
         .on_tray_icon_event(|tray, event| {
@@ -50,3 +62,126 @@ pub fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Rebuild the tray menu, e.g. after templates were added or the active one changed.
+/// Also called when the microphone opens or closes, so the tray always says so: the icon
+/// carries a red dot for as long as a capture stream is open.
+pub fn refresh_menu(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        tray.set_menu(Some(build_menu(app)?))?;
+        let label = mic_label(app);
+        tray.set_tooltip(Some(label.unwrap_or("OpenSW")))?;
+        if let Some(icon) = app.default_window_icon() {
+            tray.set_icon(Some(tray_icon(icon, label.is_some())))?;
+        }
+    }
+    Ok(())
+}
+
+/// The app icon, with the recording dot in its lower right corner while `recording`.
+fn tray_icon(icon: &Image, recording: bool) -> Image<'static> {
+    let mut rgba = icon.rgba().to_vec();
+    if recording {
+        add_recording_dot(&mut rgba, icon.width(), icon.height());
+    }
+    Image::new_owned(rgba, icon.width(), icon.height())
+}
+
+/// Paint an opaque red disc over the lower right corner, with a white rim so it stands
+/// out on red icons and dark panels alike.
+fn add_recording_dot(rgba: &mut [u8], width: u32, height: u32) {
+    let size = width.min(height) as f32;
+    let radius = size * RECORDING_DOT_RADIUS;
+    let rim = (size / 16.0).max(1.0);
+    let (cx, cy) = (width as f32 - radius - 0.5, height as f32 - radius - 0.5);
+    for (i, pixel) in rgba.chunks_exact_mut(4).enumerate() {
+        let x = (i as u32 % width) as f32;
+        let y = (i as u32 / width) as f32;
+        let distance = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+        if distance <= radius - rim {
+            pixel.copy_from_slice(&RECORDING_RED);
+        } else if distance <= radius {
+            pixel.copy_from_slice(&[255, 255, 255, 255]);
+        }
+    }
+}
+
+fn mic_label<R: Runtime, M: Manager<R>>(app: &M) -> Option<&'static str> {
+    match app.state::<AudioState>().mic_status() {
+        MicStatus::Off => None,
+        MicStatus::PreRoll => Some("● Microphone on (pre-roll)"),
+        MicStatus::Recording => Some("● Recording"),
+        MicStatus::Paused => Some("❚❚ Recording paused"),
+    }
+}
+
+fn build_menu<R: Runtime, M: Manager<R>>(app: &M) -> tauri::Result<Menu<R>> {
+    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let show_item = MenuItem::with_id(app, "show", "Show Window", true, None::<&str>)?;
+
+    let store = app.state::<TemplateStore>();
+    let active = store.active_id();
+    let template_items = store
+        .list()
+        .into_iter()
+        .map(|t| {
+            CheckMenuItem::with_id(
+                app,
+                format!("{}{}", TEMPLATE_PREFIX, t.id),
+                &t.name,
+                true,
+                active.as_deref() == Some(t.id.as_str()),
+                None::<&str>,
+            )
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let template_refs: Vec<&dyn IsMenuItem<R>> = template_items
+        .iter()
+        .map(|item| item as &dyn IsMenuItem<R>)
+        .collect();
+    let template_menu = Submenu::with_items(app, "Template", true, &template_refs)?;
+    let separator = PredefinedMenuItem::separator(app)?;
+
+    // Status only; disabled so it can't be clicked.
+    let mic_item = mic_label(app)
+        .map(|label| MenuItem::with_id(app, "mic_status", label, false, None::<&str>))
+        .transpose()?;
+    let mic_separator = PredefinedMenuItem::separator(app)?;
+
+    let mut items: Vec<&dyn IsMenuItem<R>> = Vec::new();
+    if let Some(mic_item) = &mic_item {
+        items.push(mic_item);
+        items.push(&mic_separator);
+    }
+    items.extend([
+        &show_item as &dyn IsMenuItem<R>,
+        &template_menu,
+        &separator,
+        &quit_item,
+    ]);
+    Menu::with_items(app, &items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_dot_covers_the_lower_right_corner_only() {
+        let (width, height) = (32, 32);
+        let mut rgba = vec![0u8; (width * height * 4) as usize];
+        add_recording_dot(&mut rgba, width, height);
+        let pixel = |x: u32, y: u32| {
+            let i = ((y * width + x) * 4) as usize;
+            [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+        };
+        // Centre of the dot, 0.3 * 32 = 9.6 px from the corner.
+        assert_eq!(pixel(22, 22), RECORDING_RED);
+        // Its rim.
+        assert_eq!(pixel(22, 13), [255, 255, 255, 255]);
+        // The rest of the icon.
+        assert_eq!(pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(31, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(0, 31), [0, 0, 0, 0]);
+    }
+}
@@ -2,47 +2,187 @@ import { useState, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { sendNotification } from '@tauri-apps/plugin-notification';
-import OllamaSettings from './OllamaSettings';
+import { errorCode, errorMessage } from '../errors';
+import CaptureSettings, {
+  buildCaptureConfig,
+  CaptureConfig,
+  ChannelSelection,
+  defaultCaptureConfig,
+  defaultChannelSelection,
+  selectedChannels,
+} from './CaptureSettings';
+import DiagnosticsSettings, { applySavedLogLevel } from './DiagnosticsSettings';
+import NoiseSettingsPanel, {
+  buildDspSettings,
+  defaultNoiseSettings,
+  NoiseSettings,
+} from './NoiseSettings';
+import OllamaSettings, {
+  buildOllamaConfig,
+  buildProviderConfig,
+  defaultOllamaConnection,
+  OllamaConnection,
+  RefineProviderKind,
+} from './OllamaSettings';
 import RecordingStatus from './RecordingStatus';
+import TemplateSettings from './TemplateSettings';
 import UpdateChecker from './UpdateChecker';
 
 interface AudioLevelEvent {
   amplitude: number;
+  peak: number;
+  rms_dbfs: number;
+  peak_dbfs: number;
+  clipping: boolean;
+  channels: number[];
+  bands: number[];
 }
 
-type PipelineStage = 'idle' | 'recording' | 'transcribing' | 'refining' | 'copying' | 'done';
+interface AudioHost {
+  id: string;
+  name: string;
+  default: boolean;
+}
+
+interface InputDevice {
+  id: string;
+  name: string;
+}
+
+interface RecordingStarted {
+  device_name: string;
+  fallback: boolean;
+}
+
+interface RecordingDeviceEvent {
+  device_name: string | null;
+  message: string;
+}
+
+interface RecordingLimitEvent {
+  remaining_secs: number;
+  message: string;
+}
+
+interface RecordingPausedEvent {
+  paused: boolean;
+}
+
+interface RefinementTokenEvent {
+  token: string;
+}
+
+interface RefineResult {
+  text: string;
+  path: 'refined' | 'fallback';
+  reason: string | null;
+}
+
+interface ToggleRecordingEvent {
+  template_id: string | null;
+}
+
+interface ModelInfo {
+  path: string;
+  model_type: string;
+  multilingual: boolean;
+  quantization: string;
+}
+
+type PipelineStage =
+  | 'idle'
+  | 'recording'
+  | 'paused'
+  | 'transcribing'
+  | 'refining'
+  | 'copying'
+  | 'done';
+
+/** The stream is open for a recording, whether or not it is paused. */
+const isCapturing = (stage: PipelineStage) => stage === 'recording' || stage === 'paused';
 
 const IS_MACOS = navigator.userAgent.includes('Mac');
 
 export default function AudioRecorder() {
   const [pipelineStage, setPipelineStage] = useState<PipelineStage>('idle');
   const [, setAudioLevel] = useState(0);
+  const [channelLevels, setChannelLevels] = useState<number[]>([]);
+  const [bandLevels, setBandLevels] = useState<number[]>([]);
+  const [clipping, setClipping] = useState(false);
   const [status, setStatus] = useState('Ready');
   const [, setDeviceName] = useState<string>('');
-  const [devices, setDevices] = useState<string[]>([]);
+  const [hosts, setHosts] = useState<AudioHost[]>([]);
+  const [devices, setDevices] = useState<InputDevice[]>([]);
+  // Device ID; empty for the system default
   const [selectedDevice, setSelectedDevice] = useState<string>(
     () => localStorage.getItem('selectedDevice') || ''
   );
+  const [captureConfig, setCaptureConfig] = useState<CaptureConfig>(() => ({
+    ...defaultCaptureConfig,
+    ...JSON.parse(localStorage.getItem('captureConfig') || '{}'),
+  }));
+  // Keyed by device ID; '' is the system default device
+  const [channelSelections, setChannelSelections] = useState<Record<string, ChannelSelection>>(
+    () => JSON.parse(localStorage.getItem('channelSelections') || '{}')
+  );
+  const channelSelection = channelSelections[selectedDevice] ?? defaultChannelSelection;
+  // Seconds kept from before the shortcut is pressed; 0 keeps the microphone closed
+  const [noiseSettings, setNoiseSettings] = useState<NoiseSettings>(() => ({
+    ...defaultNoiseSettings,
+    ...JSON.parse(localStorage.getItem('noiseSettings') || '{}'),
+  }));
+  const [prerollSecs, setPrerollSecs] = useState(() =>
+    Number(localStorage.getItem('prerollSecs') || 0)
+  );
   const [modelPath, setModelPath] = useState(() => localStorage.getItem('modelPath') || '');
+  const [modelInfo, setModelInfo] = useState<ModelInfo | null>(null);
   const [, setTranscription] = useState('');
   const [language, setLanguage] = useState('ja');
   const [ollamaModel, setOllamaModel] = useState(() => localStorage.getItem('ollamaModel') || '');
-  const [ollamaPrompt, setOllamaPrompt] = useState(
-    () =>
-      localStorage.getItem('ollamaPrompt') ||
-      '以下の文章の『えー』『あの』などのフィラーを取り除き、句読点を適切に補って、自然な日本語の文章に修正してください。出力は修正後の文章のみにしてください。\n\n対象の文章: {text}'
-  );
   const [ollamaUrl, setOllamaUrl] = useState(
     () => localStorage.getItem('ollamaUrl') || 'http://localhost:11434'
   );
-  const [, setRefinedText] = useState('');
+  const [ollamaConnection, setOllamaConnection] = useState<OllamaConnection>(() => ({
+    ...defaultOllamaConnection,
+    ...JSON.parse(localStorage.getItem('ollamaConnection') || '{}'),
+  }));
+  const [refineProvider, setRefineProvider] = useState<RefineProviderKind>(
+    () => (localStorage.getItem('refineProvider') as RefineProviderKind) || 'ollama'
+  );
+  const [refineApiKey, setRefineApiKey] = useState(
+    () => localStorage.getItem('refineApiKey') || ''
+  );
+  const [speechBackend, setSpeechBackend] = useState(
+    () => localStorage.getItem('speechBackend') || 'whisper'
+  );
+  const [speechServerUrl, setSpeechServerUrl] = useState(
+    () => localStorage.getItem('speechServerUrl') || 'http://localhost:8000'
+  );
+  const [speechServerModel, setSpeechServerModel] = useState(
+    () => localStorage.getItem('speechServerModel') || 'whisper-1'
+  );
+  const [speechApiKey, setSpeechApiKey] = useState(
+    () => localStorage.getItem('speechApiKey') || ''
+  );
+  const [refinedText, setRefinedText] = useState('');
   const [loadingDevices, setLoadingDevices] = useState(false);
 
   const pipelineStageRef = useRef(pipelineStage);
   const selectedDeviceRef = useRef(selectedDevice);
+  const captureConfigRef = useRef(captureConfig);
+  const noiseSettingsRef = useRef(noiseSettings);
+  const channelSelectionRef = useRef(channelSelection);
   const ollamaModelRef = useRef(ollamaModel);
-  const ollamaPromptRef = useRef(ollamaPrompt);
   const ollamaUrlRef = useRef(ollamaUrl);
+  const ollamaConnectionRef = useRef(ollamaConnection);
+  // Template chosen by a per-template shortcut for the current dictation
+  const templateIdRef = useRef<string | null>(null);
+  const refineProviderRef = useRef(refineProvider);
+  const refineApiKeyRef = useRef(refineApiKey);
+  const speechBackendRef = useRef(speechBackend);
+  const speechServerUrlRef = useRef(speechServerUrl);
+  const speechServerModelRef = useRef(speechServerModel);
+  const speechApiKeyRef = useRef(speechApiKey);
 
   const [computeDevices, setComputeDevices] = useState<{ name: string; device_type: string }[]>([]);
   const [selectedComputeDevice, setSelectedComputeDevice] = useState<string>(
@@ -66,6 +206,7 @@ export default function AudioRecorder() {
         }
       })
       .catch(console.error);
+    invoke<AudioHost[]>('get_audio_hosts').then(setHosts).catch(console.error);
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, []); // Run once on mount
 
@@ -79,26 +220,109 @@ export default function AudioRecorder() {
   }, [selectedDevice]);
   useEffect(() => {
     ollamaModelRef.current = ollamaModel;
-    ollamaPromptRef.current = ollamaPrompt;
     ollamaUrlRef.current = ollamaUrl;
     localStorage.setItem('ollamaModel', ollamaModel);
-    localStorage.setItem('ollamaPrompt', ollamaPrompt);
     localStorage.setItem('ollamaUrl', ollamaUrl);
-  }, [ollamaModel, ollamaPrompt, ollamaUrl]);
+  }, [ollamaModel, ollamaUrl]);
+  useEffect(() => {
+    ollamaConnectionRef.current = ollamaConnection;
+    localStorage.setItem('ollamaConnection', JSON.stringify(ollamaConnection));
+  }, [ollamaConnection]);
+  useEffect(() => {
+    // Prompts used to live in localStorage; move them into the backend template store once.
+    const prompt = localStorage.getItem('ollamaPrompt');
+    if (prompt === null) return;
+    const chatMode = localStorage.getItem('ollamaChatMode') === 'true';
+    invoke('save_template', {
+      template: {
+        id: 'default',
+        name: 'Default',
+        prompt,
+        system_prompt: chatMode ? localStorage.getItem('ollamaSystemPrompt') || '' : '',
+        examples: JSON.parse(localStorage.getItem('ollamaExamples') || '[]'),
+        shortcut: null,
+      },
+    })
+      .then(() => {
+        ['ollamaPrompt', 'ollamaChatMode', 'ollamaSystemPrompt', 'ollamaExamples'].forEach((key) =>
+          localStorage.removeItem(key)
+        );
+      })
+      .catch(console.error);
+  }, []);
+  useEffect(() => {
+    // Load the refinement model now so the first dictation doesn't wait for it.
+    if (refineProvider !== 'ollama' || !ollamaModel) return;
+    invoke('preload_ollama_model', {
+      config: buildOllamaConfig(ollamaUrl, ollamaConnection),
+      model: ollamaModel,
+    }).catch((err) => console.warn('Failed to preload Ollama model:', err));
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, []); // Run once on mount
+  useEffect(() => {
+    refineProviderRef.current = refineProvider;
+    refineApiKeyRef.current = refineApiKey;
+    localStorage.setItem('refineProvider', refineProvider);
+    localStorage.setItem('refineApiKey', refineApiKey);
+  }, [refineProvider, refineApiKey]);
+  useEffect(() => {
+    speechBackendRef.current = speechBackend;
+    speechServerUrlRef.current = speechServerUrl;
+    speechServerModelRef.current = speechServerModel;
+    speechApiKeyRef.current = speechApiKey;
+    localStorage.setItem('speechBackend', speechBackend);
+    localStorage.setItem('speechServerUrl', speechServerUrl);
+    localStorage.setItem('speechServerModel', speechServerModel);
+    localStorage.setItem('speechApiKey', speechApiKey);
+  }, [speechBackend, speechServerUrl, speechServerModel, speechApiKey]);
   useEffect(() => {
     localStorage.setItem('selectedDevice', selectedDevice);
   }, [selectedDevice]);
+  useEffect(() => {
+    captureConfigRef.current = captureConfig;
+    localStorage.setItem('captureConfig', JSON.stringify(captureConfig));
+  }, [captureConfig]);
+  useEffect(() => {
+    fetchDevices();
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [captureConfig.host]);
+  // Poll for plugged in devices only while the device list is on screen.
+  useEffect(() => {
+    invoke('set_device_watch', { enabled: activeTab === 'settings' }).catch(console.error);
+  }, [activeTab]);
+  useEffect(() => {
+    noiseSettingsRef.current = noiseSettings;
+    localStorage.setItem('noiseSettings', JSON.stringify(noiseSettings));
+  }, [noiseSettings]);
+  useEffect(() => {
+    channelSelectionRef.current = channelSelection;
+    localStorage.setItem('channelSelections', JSON.stringify(channelSelections));
+  }, [channelSelections, channelSelection]);
+  useEffect(() => {
+    localStorage.setItem('prerollSecs', String(prerollSecs));
+    // Must match what startRecording sends, or the warm stream can't be reused
+    invoke('set_warm_capture', {
+      enabled: prerollSecs > 0,
+      deviceId: selectedDevice || null,
+      capture: buildCaptureConfig(captureConfig, channelSelection),
+      prerollSecs,
+    }).catch((error) => setStatus(`Pre-roll error: ${errorMessage(error)}`));
+  }, [prerollSecs, selectedDevice, captureConfig, channelSelection]);
   useEffect(() => {
     localStorage.setItem('modelPath', modelPath);
   }, [modelPath]);
 
   useEffect(() => {
+    applySavedLogLevel();
     const savedPath = localStorage.getItem('modelPath');
     if (savedPath) {
-      invoke<string>('load_model', { path: savedPath })
-        .then(() => setStatus('Model loaded'))
-        .catch(() => {
-          setStatus('Model file not found');
+      invoke<ModelInfo>('load_model', { path: savedPath })
+        .then((info) => {
+          setModelInfo(info);
+          setStatus('Model loaded');
+        })
+        .catch((error) => {
+          setStatus(`Model error: ${errorMessage(error)}`);
           setModelPath('');
         });
     }
@@ -116,11 +340,26 @@ export default function AudioRecorder() {
     }
   }, [pipelineStage]);
 
+  // Older versions saved the device's display name; switch to its stable ID.
+  const migrateSelectedDevice = (deviceList: InputDevice[]) => {
+    const saved = selectedDeviceRef.current;
+    const match = deviceList.find((d) => d.name === saved && d.id !== saved);
+    if (!saved || !match || deviceList.some((d) => d.id === saved)) return;
+    setSelectedDevice(match.id);
+    setChannelSelections((prev) => {
+      const { [saved]: selection, ...rest } = prev;
+      return selection ? { ...rest, [match.id]: selection } : prev;
+    });
+  };
+
   const fetchDevices = async () => {
     setLoadingDevices(true);
     try {
-      const deviceList = await invoke<string[]>('get_input_devices');
+      const deviceList = await invoke<InputDevice[]>('get_input_devices', {
+        host: captureConfigRef.current.host || null,
+      });
       setDevices(deviceList);
+      migrateSelectedDevice(deviceList);
     } catch (err) {
       console.error('Failed to fetch devices:', err);
     } finally {
@@ -128,19 +367,33 @@ export default function AudioRecorder() {
     }
   };
 
+  // Device IDs belong to a host, so start from the new host's default device.
+  const changeHost = (host: string) => {
+    setCaptureConfig({ ...captureConfig, host });
+    setSelectedDevice('');
+  };
+
   const startRecording = async () => {
     try {
       await invoke('set_window_mode', { mode: 'compact' });
-      const device = await invoke<string>('start_recording', {
-        deviceName: selectedDeviceRef.current || null,
+      const started = await invoke<RecordingStarted>('start_recording', {
+        deviceId: selectedDeviceRef.current || null,
+        capture: buildCaptureConfig(captureConfigRef.current, channelSelectionRef.current),
       });
       setPipelineStage('recording');
-      setDeviceName(device);
+      setDeviceName(started.device_name);
       setStatus('Recording...');
+      if (started.fallback) {
+        // The saved microphone is unplugged; it is used again once it comes back.
+        sendNotification({
+          title: 'OpenSW',
+          body: `Selected microphone not found, recording with ${started.device_name}`,
+        });
+      }
     } catch (error) {
       console.error('Failed to start recording:', error);
-      setStatus(`Error: ${error}`);
       await invoke('set_window_mode', { mode: 'normal' });
+      setStatus(`Error: ${errorMessage(error)}`);
     }
   };
 
@@ -149,26 +402,52 @@ export default function AudioRecorder() {
       setPipelineStage('transcribing');
       await invoke<string>('stop_recording');
       setAudioLevel(0);
+      setChannelLevels([]);
+      setBandLevels([]);
+      setClipping(false);
       setStatus('Transcribing...');
 
       const text = await invoke<string>('transcribe_audio', {
         language: 'ja',
         useGpu: selectedComputeDeviceRef.current === 'gpu' || IS_MACOS,
+        backend:
+          speechBackendRef.current === 'openai'
+            ? {
+                kind: 'open_ai',
+                base_url: speechServerUrlRef.current,
+                model: speechServerModelRef.current,
+                api_key: speechApiKeyRef.current || null,
+              }
+            : { kind: 'whisper' },
+        dsp: buildDspSettings(noiseSettingsRef.current),
       });
       setTranscription(text);
 
       let finalText = text;
+      let usedFallback = false;
 
       if (ollamaModelRef.current) {
         setPipelineStage('refining');
         setStatus('Refining...');
-        finalText = await invoke<string>('refine_text_with_ollama', {
-          baseUrl: ollamaUrlRef.current,
+        setRefinedText('');
+        const result = await invoke<RefineResult>('refine_text', {
+          provider: buildProviderConfig(
+            refineProviderRef.current,
+            ollamaUrlRef.current,
+            refineApiKeyRef.current,
+            ollamaConnectionRef.current
+          ),
           text: text,
           model: ollamaModelRef.current,
-          prompt: ollamaPromptRef.current,
+          language: 'ja',
+          templateId: templateIdRef.current,
         });
-        setRefinedText(finalText);
+        finalText = result.text;
+        usedFallback = result.path === 'fallback';
+        if (usedFallback) {
+          console.warn('Refinement fell back to the raw transcript:', result.reason);
+        }
+        setRefinedText(usedFallback ? '' : finalText);
       }
 
       setPipelineStage('copying');
@@ -176,46 +455,105 @@ export default function AudioRecorder() {
       await invoke('copy_to_clipboard', { text: finalText });
 
       setPipelineStage('done');
-      setStatus('Copied to clipboard!');
+      setStatus(
+        usedFallback ? 'Copied raw transcript (refinement skipped)' : 'Copied to clipboard!'
+      );
 
       sendNotification({ title: 'OpenSW', body: 'Transcription copied to clipboard' });
     } catch (error) {
       console.error('Pipeline failed:', error);
-      setStatus(`Error: ${error}`);
+      setStatus(`Error: ${errorMessage(error)}`);
       await invoke('set_window_mode', { mode: 'normal' });
       setPipelineStage('idle');
+      if (errorCode(error) === 'no_model_selected') {
+        setActiveTab('settings');
+      }
     }
   };
 
   useEffect(() => {
     const unlistenAudioLevel = listen<AudioLevelEvent>('audio-level-update', (event) => {
       setAudioLevel(event.payload.amplitude);
+      setChannelLevels(event.payload.channels);
+      setBandLevels(event.payload.bands);
+      setClipping(event.payload.clipping);
+    });
+
+    const unlistenRefinementToken = listen<RefinementTokenEvent>('refinement-token', (event) => {
+      setRefinedText((prev) => prev + event.payload.token);
     });
 
-    const unlistenToggle = listen('toggle-recording', async () => {
+    const unlistenToggle = listen<ToggleRecordingEvent>('toggle-recording', async (event) => {
       if (pipelineStageRef.current === 'idle') {
+        templateIdRef.current = event.payload.template_id;
         startRecording();
-      } else if (pipelineStageRef.current === 'recording') {
+      } else if (isCapturing(pipelineStageRef.current)) {
         stopAndProcess();
       }
     });
 
-    fetchDevices();
+    const unlistenDevices = listen<InputDevice[]>('input-devices-changed', (event) => {
+      setDevices(event.payload);
+    });
+
+    const unlistenDeviceChanged = listen<RecordingDeviceEvent>(
+      'recording-device-changed',
+      (event) => {
+        setDeviceName(event.payload.device_name ?? '');
+        sendNotification({ title: 'OpenSW', body: event.payload.message });
+      }
+    );
+
+    // No device could take over; transcribe what was captured before the unplug.
+    const unlistenInterrupted = listen<RecordingDeviceEvent>('recording-interrupted', (event) => {
+      sendNotification({ title: 'OpenSW', body: event.payload.message });
+      if (isCapturing(pipelineStageRef.current)) {
+        stopAndProcess();
+      }
+    });
+
+    // Sent shortly before the maximum duration or memory cap is reached.
+    const unlistenLimitWarning = listen<RecordingLimitEvent>('recording-limit-warning', (event) => {
+      sendNotification({ title: 'OpenSW', body: event.payload.message });
+    });
+
+    const unlistenLimitReached = listen<RecordingLimitEvent>('recording-limit-reached', (event) => {
+      sendNotification({ title: 'OpenSW', body: event.payload.message });
+      if (isCapturing(pipelineStageRef.current)) {
+        stopAndProcess();
+      }
+    });
+
+    const unlistenPaused = listen<RecordingPausedEvent>('recording-paused', (event) => {
+      if (isCapturing(pipelineStageRef.current)) {
+        setPipelineStage(event.payload.paused ? 'paused' : 'recording');
+        setStatus(event.payload.paused ? 'Paused' : 'Recording...');
+      }
+    });
 
     return () => {
+      unlistenDevices.then((f) => f());
+      unlistenDeviceChanged.then((f) => f());
+      unlistenInterrupted.then((f) => f());
+      unlistenPaused.then((f) => f());
+      unlistenLimitWarning.then((f) => f());
+      unlistenLimitReached.then((f) => f());
       unlistenAudioLevel.then((f) => f());
+      unlistenRefinementToken.then((f) => f());
       unlistenToggle.then((f) => f());
     };
   }, []);
 
   const selectModel = async () => {
     try {
-      const path = await invoke<string>('select_model');
-      setModelPath(path);
+      const info = await invoke<ModelInfo>('select_model');
+      setModelPath(info.path);
+      setModelInfo(info);
       setStatus('Model loaded');
     } catch (error) {
+      if (errorCode(error) === 'no_file_selected') return;
       console.error('Failed to select model:', error);
-      setStatus(`Error: ${error}`);
+      setStatus(`Error: ${errorMessage(error)}`);
     }
   };
 
@@ -223,11 +561,26 @@ export default function AudioRecorder() {
     return (
       <RecordingStatus
         stage={pipelineStage}
+        preview={pipelineStage === 'refining' ? refinedText : ''}
+        channelLevels={channelLevels}
+        bandLevels={bandLevels}
+        clipping={clipping}
+        activeChannels={selectedChannels(
+          // Only the microphone on its own sends its channels; the others are mixed.
+          captureConfig.source === 'microphone' ? channelSelection : defaultChannelSelection,
+          channelLevels.length
+        )}
         onStop={() => {
-          if (pipelineStage === 'recording') {
+          if (isCapturing(pipelineStage)) {
             invoke('request_toggle_recording').catch(console.error);
+          } else if (pipelineStage === 'refining') {
+            invoke('stop_refinement').catch(console.error);
           }
         }}
+        onPauseToggle={() => {
+          const command = pipelineStage === 'paused' ? 'resume_recording' : 'pause_recording';
+          invoke(command).catch(console.error);
+        }}
       />
     );
   }
@@ -292,6 +645,26 @@ export default function AudioRecorder() {
           <div className="settings-panel">
             <section className="settings-section">
               <h2>Audio Input</h2>
+              {hosts.length > 1 && (
+                <div className="setting-row">
+                  <label>Host</label>
+                  <div className="input-group">
+                    <div className="select-wrapper">
+                      <select
+                        value={captureConfig.host}
+                        onChange={(e) => changeHost(e.target.value)}
+                      >
+                        {hosts.map((host) => (
+                          <option key={host.id} value={host.default ? '' : host.id}>
+                            {host.name}
+                          </option>
+                        ))}
+                      </select>
+                      <span className="select-arrow">▼</span>
+                    </div>
+                  </div>
+                </div>
+              )}
               <div className="setting-row">
                 <label>Device</label>
                 <div className="input-group">
@@ -302,8 +675,8 @@ export default function AudioRecorder() {
                     >
                       <option value="">System Default</option>
                       {devices.map((device) => (
-                        <option key={device} value={device}>
-                          {device}
+                        <option key={device.id} value={device.id}>
+                          {device.name}
                         </option>
                       ))}
                     </select>
@@ -319,10 +692,104 @@ export default function AudioRecorder() {
                   </button>
                 </div>
               </div>
+              <CaptureSettings
+                deviceId={selectedDevice}
+                config={captureConfig}
+                onChange={setCaptureConfig}
+                channelSelection={channelSelection}
+                onChannelSelectionChange={(selection) =>
+                  setChannelSelections({ ...channelSelections, [selectedDevice]: selection })
+                }
+              />
+              <div className="setting-row">
+                <label>Pre-roll</label>
+                <div className="input-group">
+                  <div className="select-wrapper">
+                    <select
+                      value={prerollSecs}
+                      onChange={(e) => setPrerollSecs(Number(e.target.value))}
+                    >
+                      <option value={0}>Off</option>
+                      {[1, 2, 3, 5].map((secs) => (
+                        <option key={secs} value={secs}>
+                          {secs} s
+                        </option>
+                      ))}
+                    </select>
+                    <span className="select-arrow">▼</span>
+                  </div>
+                </div>
+              </div>
+              {prerollSecs > 0 && (
+                <div className="status-msg">
+                  The microphone stays on while idle so recordings include the moment before
+                  the shortcut. The tray menu shows while it is on.
+                </div>
+              )}
+            </section>
+
+            <section className="settings-section">
+              <h2>Noise Reduction</h2>
+              <NoiseSettingsPanel settings={noiseSettings} onChange={setNoiseSettings} />
             </section>
 
             <section className="settings-section">
               <h2>Whisper Model</h2>
+              <div className="setting-row">
+                <label>Engine</label>
+                <div className="input-group">
+                  <div className="select-wrapper">
+                    <select
+                      value={speechBackend}
+                      onChange={(e) => setSpeechBackend(e.target.value)}
+                    >
+                      <option value="whisper">Local Whisper</option>
+                      <option value="openai">OpenAI-compatible Server</option>
+                    </select>
+                    <span className="select-arrow">▼</span>
+                  </div>
+                </div>
+              </div>
+              {speechBackend === 'openai' && (
+                <>
+                  <div className="setting-row">
+                    <label>Server URL</label>
+                    <div className="input-group">
+                      <input
+                        type="text"
+                        value={speechServerUrl}
+                        onChange={(e) => setSpeechServerUrl(e.target.value)}
+                        placeholder="http://localhost:8000"
+                        className="text-input"
+                      />
+                    </div>
+                  </div>
+                  <div className="setting-row">
+                    <label>Server Model</label>
+                    <div className="input-group">
+                      <input
+                        type="text"
+                        value={speechServerModel}
+                        onChange={(e) => setSpeechServerModel(e.target.value)}
+                        placeholder="whisper-1"
+                        className="text-input"
+                      />
+                    </div>
+                  </div>
+                  <div className="setting-row">
+                    <label>API Key</label>
+                    <div className="input-group">
+                      <input
+                        type="password"
+                        value={speechApiKey}
+                        onChange={(e) => setSpeechApiKey(e.target.value)}
+                        placeholder="Optional"
+                        className="text-input"
+                      />
+                    </div>
+                  </div>
+                </>
+              )}
               <div className="setting-row">
                 <label>Model File</label>
                 <div className="file-selector">
@@ -334,6 +801,15 @@ export default function AudioRecorder() {
                   </span>
                 </div>
               </div>
+              {modelInfo && (
+                <div className="setting-row">
+                  <label>Model Info</label>
+                  <span className="file-name">
+                    {modelInfo.model_type} · {modelInfo.quantization} ·{' '}
+                    {modelInfo.multilingual ? 'Multilingual' : 'English-only'}
+                  </span>
+                </div>
+              )}
               {!IS_MACOS && (
                 <div className="setting-row">
                   <label>Inference Device</label>
@@ -357,17 +833,31 @@ export default function AudioRecorder() {
             </section>
 
             <section className="settings-section">
-              <h2>Text Refinement (Ollama)</h2>
+              <h2>Text Refinement</h2>
               <OllamaSettings
+                provider={refineProvider}
+                onProviderChange={setRefineProvider}
+                apiKey={refineApiKey}
+                onApiKeyChange={setRefineApiKey}
                 selectedModel={ollamaModel}
                 onModelChange={setOllamaModel}
-                prompt={ollamaPrompt}
-                onPromptChange={setOllamaPrompt}
                 baseUrl={ollamaUrl}
                 onBaseUrlChange={setOllamaUrl}
+                connection={ollamaConnection}
+                onConnectionChange={setOllamaConnection}
               />
             </section>
 
+            <section className="settings-section">
+              <h2>Refinement Templates</h2>
+              <TemplateSettings />
+            </section>
+
+            <section className="settings-section">
+              <h2>Diagnostics</h2>
+              <DiagnosticsSettings />
+            </section>
+
             <section className="settings-section">
               <h2>App Updates</h2>
               <UpdateChecker />
@@ -564,6 +1054,20 @@ export default function AudioRecorder() {
           opacity: 0.4;
           cursor: not-allowed;
         }
+        .text-input {
+          flex: 1;
+          max-width: 280px;
+          background: rgba(255, 255, 255, 0.08);
+          border: 1px solid rgba(255, 255, 255, 0.1);
+          color: #fff;
+          padding: 8px 12px;
+          border-radius: 6px;
+          font-size: 0.95em;
+        }
+        .text-input:focus {
+          outline: none;
+          border-color: #646cff;
+        }
         .file-selector {
           display: flex;
           align-items: center;
@@ -0,0 +1,531 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { errorMessage } from '../errors';
+
+/** Capture format requested from the device; `null` keeps the device default. */
+export interface CaptureConfig {
+  sampleRate: number | null;
+  channels: number | null;
+  bufferSize: number | null;
+  /** Level meter updates per second. */
+  meterRateHz: number | null;
+  /** Fixed input gain in dB, applied to the meter and to transcription. */
+  gainDb: number;
+  /** Loudness normalization before transcription. */
+  normalize: Normalization;
+  /** Automatic gain control. */
+  agc: boolean;
+  /** Recordings are stopped and transcribed after this many minutes; `null` is unlimited. */
+  maxDurationMin: number | null;
+  /** Cap on the in-memory recording buffer; `null` is unlimited. */
+  maxMemoryMb: number | null;
+  /** Write long recordings to disk as they are captured. */
+  spillToDisk: boolean;
+  source: CaptureSourceKind;
+  /** Output device recorded as system audio; empty for the default output. */
+  systemDeviceId: string;
+  /** Levels of the two sources in the mixed source, in dB. */
+  micGainDb: number;
+  systemGainDb: number;
+  /** Audio host from get_audio_hosts; empty for the platform default. */
+  host: string;
+}
+
+export type CaptureSourceKind = 'microphone' | 'system' | 'mixed';
+
+export type Normalization = 'off' | 'peak' | 'loudness';
+
+export const defaultCaptureConfig: CaptureConfig = {
+  sampleRate: null,
+  channels: null,
+  bufferSize: null,
+  meterRateHz: null,
+  gainDb: 0,
+  normalize: 'off',
+  agc: false,
+  maxDurationMin: 30,
+  maxMemoryMb: 1024,
+  spillToDisk: false,
+  source: 'microphone',
+  systemDeviceId: '',
+  micGainDb: 0,
+  systemGainDb: 0,
+  host: '',
+};
+
+/** Input channels (zero-based) mixed into the transcribed signal. */
+export type ChannelSelection =
+  | { mode: 'average' }
+  | { mode: 'single'; channel: number }
+  | { mode: 'pair'; left: number; right: number };
+
+export const defaultChannelSelection: ChannelSelection = { mode: 'average' };
+
+/** Channels that end up in the mix, for highlighting them in the level meter. */
+export const selectedChannels = (selection: ChannelSelection, count: number) => {
+  switch (selection.mode) {
+    case 'single':
+      return [selection.channel];
+    case 'pair':
+      return [selection.left, selection.right];
+    default:
+      return [...Array(count).keys()];
+  }
+};
+
+const encodeSelection = (selection: ChannelSelection) => {
+  switch (selection.mode) {
+    case 'single':
+      return `single:${selection.channel}`;
+    case 'pair':
+      return `pair:${selection.left}:${selection.right}`;
+    default:
+      return 'average';
+  }
+};
+
+const decodeSelection = (value: string): ChannelSelection => {
+  const [mode, a, b] = value.split(':');
+  if (mode === 'single') return { mode, channel: Number(a) };
+  if (mode === 'pair') return { mode, left: Number(a), right: Number(b) };
+  return defaultChannelSelection;
+};
+
+const buildSource = (config: CaptureConfig) => {
+  const deviceId = config.systemDeviceId || null;
+  switch (config.source) {
+    case 'system':
+      return { kind: 'system', device_id: deviceId };
+    case 'mixed':
+      return {
+        kind: 'mixed',
+        device_id: deviceId,
+        mic_gain_db: config.micGainDb,
+        system_gain_db: config.systemGainDb,
+      };
+    default:
+      return { kind: 'microphone' };
+  }
+};
+
+export const buildCaptureConfig = (config: CaptureConfig, channelSelection: ChannelSelection) => ({
+  sample_rate: config.sampleRate,
+  channels: config.channels,
+  buffer_size: config.bufferSize,
+  channel_selection: channelSelection,
+  meter_rate_hz: config.meterRateHz,
+  gain: { gain_db: config.gainDb, normalize: config.normalize, agc: config.agc },
+  limits: {
+    max_duration_secs: config.maxDurationMin && config.maxDurationMin * 60,
+    max_memory_mb: config.maxMemoryMb,
+    spill_to_disk: config.spillToDisk,
+  },
+  source: buildSource(config),
+  host: config.host || null,
+});
+
+interface OutputDevice {
+  id: string;
+  name: string;
+}
+
+interface InputConfigRange {
+  channels: number;
+  min_sample_rate: number;
+  max_sample_rate: number;
+  sample_format: string;
+  min_buffer_size: number | null;
+  max_buffer_size: number | null;
+}
+
+interface InputConfigs {
+  default_sample_rate: number;
+  default_channels: number;
+  configs: InputConfigRange[];
+}
+
+const SAMPLE_RATES = [8000, 16000, 22050, 32000, 44100, 48000, 88200, 96000];
+const BUFFER_SIZES = [128, 256, 512, 1024, 2048, 4096];
+const METER_RATES = [10, 15, 60];
+const GAINS_DB = [-6, 0, 6, 12, 18, 24];
+const MAX_DURATIONS_MIN = [5, 15, 30, 60, 120];
+const MEMORY_CAPS_MB = [256, 512, 1024, 2048];
+
+interface CaptureSettingsProps {
+  /** Empty for the system default device. */
+  deviceId: string;
+  config: CaptureConfig;
+  onChange: (config: CaptureConfig) => void;
+  /** Stored per device, since each interface wires its mic to a different channel. */
+  channelSelection: ChannelSelection;
+  onChannelSelectionChange: (selection: ChannelSelection) => void;
+}
+
+export default function CaptureSettings({
+  deviceId,
+  config,
+  onChange,
+  channelSelection,
+  onChannelSelectionChange,
+}: CaptureSettingsProps) {
+  const [inputConfigs, setInputConfigs] = useState<InputConfigs | null>(null);
+  const [outputDevices, setOutputDevices] = useState<OutputDevice[]>([]);
+  const [error, setError] = useState('');
+  const [outputError, setOutputError] = useState('');
+
+  // Monitor sources on Linux, where they are listed through pactl.
+  useEffect(() => {
+    setOutputError('');
+    invoke<OutputDevice[]>('get_output_devices', { host: config.host || null })
+      .then(setOutputDevices)
+      .catch((err) => {
+        console.error('Failed to fetch output devices:', err);
+        setOutputDevices([]);
+        setOutputError(errorMessage(err));
+      });
+  }, [config.host]);
+
+  useEffect(() => {
+    setError('');
+    invoke<InputConfigs>('get_input_configs', {
+      host: config.host || null,
+      deviceId: deviceId || null,
+    })
+      .then(setInputConfigs)
+      .catch((err) => {
+        console.error('Failed to fetch input configs:', err);
+        setInputConfigs(null);
+        setError(errorMessage(err));
+      });
+  }, [deviceId, config.host]);
+
+  const ranges = inputConfigs?.configs ?? [];
+  const channelCounts = [...new Set(ranges.map((r) => r.channels))].sort((a, b) => a - b);
+  const channels = config.channels ?? inputConfigs?.default_channels;
+  const sampleRates = SAMPLE_RATES.filter((rate) =>
+    ranges.some(
+      (r) => r.channels === channels && r.min_sample_rate <= rate && rate <= r.max_sample_rate
+    )
+  );
+  const bufferSizes = BUFFER_SIZES.filter((size) =>
+    ranges.some(
+      (r) =>
+        r.min_buffer_size === null ||
+        r.max_buffer_size === null ||
+        (r.min_buffer_size <= size && size <= r.max_buffer_size)
+    )
+  );
+
+  const channelCount = channels ?? 0;
+  const pairs = [...Array(Math.floor(channelCount / 2)).keys()].map((i) => [2 * i, 2 * i + 1]);
+
+  const parse = (value: string) => (value ? Number(value) : null);
+
+  return (
+    <div className="capture-settings">
+      {error && <div className="error-msg">{error}</div>}
+
+      <div className="setting-row">
+        <label>Source</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.source}
+              onChange={(e) => onChange({ ...config, source: e.target.value as CaptureSourceKind })}
+            >
+              <option value="microphone">Microphone</option>
+              <option value="system">System Audio</option>
+              <option value="mixed">Microphone + System Audio</option>
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      {config.source !== 'microphone' && outputError && (
+        <div className="error-msg">{outputError}</div>
+      )}
+
+      {config.source !== 'microphone' && outputDevices.length > 0 && (
+        <div className="setting-row">
+          <label>Output Device</label>
+          <div className="input-group">
+            <div className="select-wrapper">
+              <select
+                value={config.systemDeviceId}
+                onChange={(e) => onChange({ ...config, systemDeviceId: e.target.value })}
+              >
+                <option value="">System Default</option>
+                {outputDevices.map((device) => (
+                  <option key={device.id} value={device.id}>
+                    {device.name}
+                  </option>
+                ))}
+              </select>
+              <span className="select-arrow">▼</span>
+            </div>
+          </div>
+        </div>
+      )}
+
+      {config.source === 'mixed' && (
+        <>
+          <div className="setting-row">
+            <label>Microphone Level</label>
+            <div className="input-group">
+              <div className="select-wrapper">
+                <select
+                  value={config.micGainDb}
+                  onChange={(e) => onChange({ ...config, micGainDb: Number(e.target.value) })}
+                >
+                  {GAINS_DB.map((db) => (
+                    <option key={db} value={db}>
+                      {db > 0 ? `+${db}` : db} dB
+                    </option>
+                  ))}
+                </select>
+                <span className="select-arrow">▼</span>
+              </div>
+            </div>
+          </div>
+          <div className="setting-row">
+            <label>System Level</label>
+            <div className="input-group">
+              <div className="select-wrapper">
+                <select
+                  value={config.systemGainDb}
+                  onChange={(e) => onChange({ ...config, systemGainDb: Number(e.target.value) })}
+                >
+                  {GAINS_DB.map((db) => (
+                    <option key={db} value={db}>
+                      {db > 0 ? `+${db}` : db} dB
+                    </option>
+                  ))}
+                </select>
+                <span className="select-arrow">▼</span>
+              </div>
+            </div>
+          </div>
+        </>
+      )}
+
+      <div className="setting-row">
+        <label>Sample Rate</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.sampleRate ?? ''}
+              onChange={(e) => onChange({ ...config, sampleRate: parse(e.target.value) })}
+            >
+              <option value="">
+                Default
+                {inputConfigs ? ` (${inputConfigs.default_sample_rate} Hz)` : ''}
+              </option>
+              {sampleRates.map((rate) => (
+                <option key={rate} value={rate}>
+                  {rate} Hz
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Channels</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.channels ?? ''}
+              onChange={(e) => onChange({ ...config, channels: parse(e.target.value) })}
+            >
+              <option value="">
+                Default{inputConfigs ? ` (${inputConfigs.default_channels})` : ''}
+              </option>
+              {channelCounts.map((count) => (
+                <option key={count} value={count}>
+                  {count}
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Buffer Size</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.bufferSize ?? ''}
+              onChange={(e) => onChange({ ...config, bufferSize: parse(e.target.value) })}
+            >
+              <option value="">Default</option>
+              {bufferSizes.map((size) => (
+                <option key={size} value={size}>
+                  {size} frames
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Meter Rate</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.meterRateHz ?? ''}
+              onChange={(e) => onChange({ ...config, meterRateHz: parse(e.target.value) })}
+            >
+              <option value="">Default (30 Hz)</option>
+              {METER_RATES.map((rate) => (
+                <option key={rate} value={rate}>
+                  {rate} Hz
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Input Gain</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.gainDb}
+              onChange={(e) => onChange({ ...config, gainDb: Number(e.target.value) })}
+            >
+              {GAINS_DB.map((db) => (
+                <option key={db} value={db}>
+                  {db > 0 ? `+${db}` : db} dB
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Automatic Gain</label>
+        <div className="input-group">
+          <input
+            type="checkbox"
+            checked={config.agc}
+            onChange={(e) => onChange({ ...config, agc: e.target.checked })}
+            title="Even out the level when speaking closer to or further from the microphone"
+          />
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Normalize</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.normalize}
+              onChange={(e) => onChange({ ...config, normalize: e.target.value as Normalization })}
+            >
+              <option value="off">Off</option>
+              <option value="peak">Peak (-1 dBFS)</option>
+              <option value="loudness">Loudness (-23 LUFS)</option>
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Max Duration</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.maxDurationMin ?? ''}
+              onChange={(e) => onChange({ ...config, maxDurationMin: parse(e.target.value) })}
+            >
+              <option value="">Unlimited</option>
+              {MAX_DURATIONS_MIN.map((minutes) => (
+                <option key={minutes} value={minutes}>
+                  {minutes} min
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Memory Cap</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={config.maxMemoryMb ?? ''}
+              onChange={(e) => onChange({ ...config, maxMemoryMb: parse(e.target.value) })}
+              disabled={config.spillToDisk}
+            >
+              <option value="">Unlimited</option>
+              {MEMORY_CAPS_MB.map((mb) => (
+                <option key={mb} value={mb}>
+                  {mb} MB
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Spill to Disk</label>
+        <div className="input-group">
+          <input
+            type="checkbox"
+            checked={config.spillToDisk}
+            onChange={(e) => onChange({ ...config, spillToDisk: e.target.checked })}
+            title="Write the recording to a temporary file instead of keeping it in memory"
+          />
+        </div>
+      </div>
+
+      {channelCount > 1 && (
+        <div className="setting-row">
+          <label>Input Channel</label>
+          <div className="input-group">
+            <div className="select-wrapper">
+              <select
+                value={encodeSelection(channelSelection)}
+                onChange={(e) => onChannelSelectionChange(decodeSelection(e.target.value))}
+              >
+                <option value="average">Average All</option>
+                {[...Array(channelCount).keys()].map((channel) => (
+                  <option key={channel} value={`single:${channel}`}>
+                    Channel {channel + 1}
+                  </option>
+                ))}
+                {channelCount > 2 &&
+                  pairs.map(([left, right]) => (
+                    <option key={left} value={`pair:${left}:${right}`}>
+                      Channels {left + 1} + {right + 1}
+                    </option>
+                  ))}
+              </select>
+              <span className="select-arrow">▼</span>
+            </div>
+          </div>
+        </div>
+      )}
+
+      {config.sampleRate === 16000 && config.channels === 1 && (
+        <div className="status-msg">Capturing at 16 kHz mono, no resampling needed</div>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,93 @@
+import { useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { errorCode, errorMessage } from '../errors';
+
+const LOG_LEVELS = ['error', 'warn', 'info', 'debug', 'trace'];
+
+/** Re-apply the log level chosen in a previous session. */
+export const applySavedLogLevel = () => {
+  const level = localStorage.getItem('logLevel');
+  if (level) {
+    invoke('set_log_level', { level }).catch(console.error);
+  }
+};
+
+/** Everything the frontend keeps in localStorage; the backend redacts secrets. */
+const collectSettings = () =>
+  Object.fromEntries(
+    Object.keys(localStorage).map((key) => {
+      const value = localStorage.getItem(key) ?? '';
+      try {
+        return [key, JSON.parse(value)];
+      } catch {
+        return [key, value];
+      }
+    })
+  );
+
+export default function DiagnosticsSettings() {
+  const [logLevel, setLogLevel] = useState(() => localStorage.getItem('logLevel') || 'info');
+  const [message, setMessage] = useState('');
+
+  const changeLogLevel = async (level: string) => {
+    try {
+      await invoke('set_log_level', { level });
+      setLogLevel(level);
+      localStorage.setItem('logLevel', level);
+    } catch (err) {
+      setMessage(errorMessage(err));
+    }
+  };
+
+  const exportDiagnostics = async () => {
+    setMessage('');
+    try {
+      const path = await invoke<string>('export_diagnostics', { settings: collectSettings() });
+      setMessage(`Saved to ${path}`);
+    } catch (err) {
+      if (errorCode(err) === 'no_file_selected') return;
+      console.error('Failed to export diagnostics:', err);
+      setMessage(errorMessage(err));
+    }
+  };
+
+  return (
+    <div className="diagnostics-settings">
+      <div className="setting-row">
+        <label>Log Level</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select value={logLevel} onChange={(e) => changeLogLevel(e.target.value)}>
+              {LOG_LEVELS.map((level) => (
+                <option key={level} value={level}>
+                  {level}
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Bug Report</label>
+        <div className="input-group">
+          <button onClick={exportDiagnostics} className="select-btn">
+            Export Diagnostics
+          </button>
+        </div>
+      </div>
+
+      {message && <div className="diagnostics-msg">{message}</div>}
+
+      <style>{`
+        .diagnostics-msg {
+          font-size: 0.8em;
+          color: rgba(255, 255, 255, 0.5);
+          padding: 0 14px 6px;
+          word-break: break-all;
+        }
+      `}</style>
+    </div>
+  );
+}
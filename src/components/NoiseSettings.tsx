@@ -0,0 +1,91 @@
+/** Cleanup run on the recording before transcription; `null` turns a stage off. */
+export interface NoiseSettings {
+  highPassHz: number | null;
+  gateThresholdDbfs: number | null;
+  denoise: boolean;
+}
+
+export const defaultNoiseSettings: NoiseSettings = {
+  highPassHz: null,
+  gateThresholdDbfs: null,
+  denoise: false,
+};
+
+export const buildDspSettings = (settings: NoiseSettings) => ({
+  high_pass_hz: settings.highPassHz,
+  gate_threshold_dbfs: settings.gateThresholdDbfs,
+  denoise: settings.denoise,
+});
+
+const HIGH_PASS_HZ = [80, 120, 200];
+const GATE_THRESHOLDS_DBFS = [-60, -50, -40];
+
+interface NoiseSettingsProps {
+  settings: NoiseSettings;
+  onChange: (settings: NoiseSettings) => void;
+}
+
+export default function NoiseSettingsPanel({ settings, onChange }: NoiseSettingsProps) {
+  const parse = (value: string) => (value ? Number(value) : null);
+
+  return (
+    <div className="noise-settings">
+      <div className="setting-row">
+        <label>High-pass Filter</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={settings.highPassHz ?? ''}
+              onChange={(e) => onChange({ ...settings, highPassHz: parse(e.target.value) })}
+            >
+              <option value="">Off</option>
+              {HIGH_PASS_HZ.map((hz) => (
+                <option key={hz} value={hz}>
+                  {hz} Hz
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Noise Gate</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={settings.gateThresholdDbfs ?? ''}
+              onChange={(e) => onChange({ ...settings, gateThresholdDbfs: parse(e.target.value) })}
+            >
+              <option value="">Off</option>
+              {GATE_THRESHOLDS_DBFS.map((db) => (
+                <option key={db} value={db}>
+                  {db} dBFS
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
+      <div className="setting-row">
+        <label>Denoise</label>
+        <div className="input-group">
+          <input
+            type="checkbox"
+            checked={settings.denoise}
+            onChange={(e) => onChange({ ...settings, denoise: e.target.checked })}
+            title="Remove keyboard and fan noise with RNNoise"
+          />
+        </div>
+      </div>
+
+      <div className="status-msg">
+        Applied to the recording before transcription. Reduces hallucinated text from background
+        noise.
+      </div>
+    </div>
+  );
+}
@@ -1,46 +1,171 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { errorCode, errorMessage } from '../errors';
+
+export type RefineProviderKind = 'ollama' | 'open_ai';
+
+/** Connection settings for an Ollama server, as edited in the form. */
+export interface OllamaConnection {
+  /** One `Name: value` header per line. */
+  headers: string;
+  proxy: string;
+  timeoutSecs: string;
+}
+
+export const defaultOllamaConnection: OllamaConnection = {
+  headers: '',
+  proxy: '',
+  timeoutSecs: '',
+};
+
+export const buildOllamaConfig = (baseUrl: string, connection: OllamaConnection) => ({
+  base_url: baseUrl,
+  headers: Object.fromEntries(
+    connection.headers
+      .split('\n')
+      .map((line) => line.split(':'))
+      .filter(([name, ...value]) => name.trim() && value.length > 0)
+      .map(([name, ...value]) => [name.trim(), value.join(':').trim()])
+  ),
+  proxy: connection.proxy.trim() || null,
+  timeout_secs: connection.timeoutSecs ? Number(connection.timeoutSecs) : null,
+});
+
+export const buildProviderConfig = (
+  kind: RefineProviderKind,
+  baseUrl: string,
+  apiKey: string,
+  connection: OllamaConnection
+) =>
+  kind === 'open_ai'
+    ? { kind, base_url: baseUrl, api_key: apiKey || null }
+    : { kind, ...buildOllamaConfig(baseUrl, connection) };
+
+interface OllamaStatus {
+  version: string;
+  model_installed: boolean | null;
+}
+
+interface PullProgress {
+  status: string;
+  digest: string | null;
+  total: number | null;
+  completed: number | null;
+}
 
 interface OllamaSettingsProps {
+  provider: RefineProviderKind;
+  onProviderChange: (provider: RefineProviderKind) => void;
+  apiKey: string;
+  onApiKeyChange: (apiKey: string) => void;
   selectedModel: string;
   onModelChange: (model: string) => void;
-  prompt: string;
-  onPromptChange: (prompt: string) => void;
   baseUrl: string;
   onBaseUrlChange: (url: string) => void;
+  connection: OllamaConnection;
+  onConnectionChange: (connection: OllamaConnection) => void;
 }
 
 export default function OllamaSettings({
+  provider,
+  onProviderChange,
+  apiKey,
+  onApiKeyChange,
   selectedModel,
   onModelChange,
-  prompt,
-  onPromptChange,
   baseUrl,
   onBaseUrlChange,
+  connection,
+  onConnectionChange,
 }: OllamaSettingsProps) {
   const [models, setModels] = useState<string[]>([]);
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState('');
+  const [status, setStatus] = useState<OllamaStatus | null>(null);
+  const [pullName, setPullName] = useState('');
+  const [pullProgress, setPullProgress] = useState<PullProgress | null>(null);
 
   useEffect(() => {
     if (baseUrl) {
       fetchModels();
     }
-  }, [baseUrl]); // eslint-disable-line react-hooks/exhaustive-deps
+  }, [baseUrl, provider, connection]); // eslint-disable-line react-hooks/exhaustive-deps
+
+  useEffect(() => {
+    if (provider === 'ollama' && baseUrl) {
+      checkStatus();
+    } else {
+      setStatus(null);
+    }
+  }, [baseUrl, provider, selectedModel, connection]); // eslint-disable-line react-hooks/exhaustive-deps
+
+  useEffect(() => {
+    const unlisten = listen<PullProgress>('ollama-pull-progress', (event) => {
+      setPullProgress(event.payload);
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const checkStatus = async () => {
+    try {
+      setStatus(
+        await invoke<OllamaStatus>('get_ollama_status', {
+          config: buildOllamaConfig(baseUrl, connection),
+          model: selectedModel || null,
+        })
+      );
+    } catch (err) {
+      console.error('Ollama status check failed:', err);
+      setStatus(null);
+      setError(
+        errorCode(err) === 'ollama_unreachable'
+          ? `Ollama is not running at ${baseUrl}. Start it with "ollama serve".`
+          : errorMessage(err)
+      );
+    }
+  };
+
+  const pullModel = async (model: string) => {
+    if (!model) return;
+    setError('');
+    setPullProgress({ status: 'starting', digest: null, total: null, completed: null });
+    try {
+      await invoke('pull_ollama_model', { config: buildOllamaConfig(baseUrl, connection), model });
+      onModelChange(model);
+      await fetchModels();
+    } catch (err) {
+      console.error('Failed to pull model:', err);
+      setError(`Failed to pull ${model}: ${errorMessage(err)}`);
+    } finally {
+      setPullProgress(null);
+    }
+  };
+
+  const pullPercent =
+    pullProgress?.total && pullProgress.completed
+      ? Math.round((pullProgress.completed / pullProgress.total) * 100)
+      : null;
 
   const fetchModels = async () => {
     if (!baseUrl) return;
     setLoading(true);
     setError('');
     try {
-      const fetchedModels = await invoke<string[]>('get_ollama_models', { baseUrl });
+      const fetchedModels = await invoke<string[]>('get_refinement_models', {
+        provider: buildProviderConfig(provider, baseUrl, apiKey, connection),
+      });
       setModels(fetchedModels);
       if (fetchedModels.length > 0 && !selectedModel) {
         onModelChange(fetchedModels[0]);
       }
     } catch (err) {
-      console.error('Failed to fetch Ollama models:', err);
-      setError('Ollama not available');
+      console.error('Failed to fetch refinement models:', err);
+      setError(
+        errorCode(err) === 'ollama_unreachable' ? 'Ollama not available' : errorMessage(err)
+      );
       setModels([]);
     } finally {
       setLoading(false);
@@ -51,6 +176,22 @@ export default function OllamaSettings({
     <div className="ollama-settings">
       {error && <div className="error-msg">{error}</div>}
 
+      <div className="setting-row">
+        <label>Provider</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={provider}
+              onChange={(e) => onProviderChange(e.target.value as RefineProviderKind)}
+            >
+              <option value="ollama">Ollama</option>
+              <option value="open_ai">OpenAI-compatible</option>
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+        </div>
+      </div>
+
       <div className="setting-row">
         <label>URL</label>
         <div className="input-group">
@@ -58,12 +199,27 @@ export default function OllamaSettings({
             type="text"
             value={baseUrl}
             onChange={(e) => onBaseUrlChange(e.target.value)}
-            placeholder="http://localhost:11434"
+            placeholder={provider === 'ollama' ? 'http://localhost:11434' : 'http://localhost:8080'}
             className="url-input"
           />
         </div>
       </div>
 
+      {provider === 'open_ai' && (
+        <div className="setting-row">
+          <label>API Key</label>
+          <div className="input-group">
+            <input
+              type="password"
+              value={apiKey}
+              onChange={(e) => onApiKeyChange(e.target.value)}
+              placeholder="Optional"
+              className="url-input"
+            />
+          </div>
+        </div>
+      )}
+
       <div className="setting-row">
         <label>Model</label>
         <div className="input-group">
@@ -93,15 +249,85 @@ export default function OllamaSettings({
         </div>
       </div>
 
-      <div className="setting-row prompt-row">
-        <label>Prompt Template</label>
-        <textarea
-          value={prompt}
-          onChange={(e) => onPromptChange(e.target.value)}
-          placeholder="Use {text} as placeholder..."
-          rows={3}
-        />
-      </div>
+      {provider === 'ollama' && (
+        <>
+          <div className="setting-row">
+            <label>Headers</label>
+            <div className="input-group">
+              <textarea
+                value={connection.headers}
+                onChange={(e) => onConnectionChange({ ...connection, headers: e.target.value })}
+                placeholder="Authorization: Bearer ..."
+                rows={2}
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>Proxy</label>
+            <div className="input-group">
+              <input
+                type="text"
+                value={connection.proxy}
+                onChange={(e) => onConnectionChange({ ...connection, proxy: e.target.value })}
+                placeholder="System default"
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>Timeout (s)</label>
+            <div className="input-group">
+              <input
+                type="number"
+                min="1"
+                value={connection.timeoutSecs}
+                onChange={(e) => onConnectionChange({ ...connection, timeoutSecs: e.target.value })}
+                placeholder="120"
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          {status && (
+            <div className="status-msg">
+              Ollama {status.version}
+              {status.model_installed === false && ` · ${selectedModel} is not installed`}
+            </div>
+          )}
+
+          <div className="setting-row">
+            <label>Pull Model</label>
+            <div className="input-group">
+              <input
+                type="text"
+                value={pullName}
+                onChange={(e) => setPullName(e.target.value)}
+                placeholder={status?.model_installed === false ? selectedModel : 'e.g. llama3.2'}
+                className="url-input"
+                disabled={!!pullProgress}
+              />
+              <button
+                onClick={() => pullModel(pullName || selectedModel)}
+                disabled={!!pullProgress || !baseUrl || !(pullName || selectedModel)}
+                className="icon-btn"
+                title="Download model"
+              >
+                ↓
+              </button>
+            </div>
+          </div>
+
+          {pullProgress && (
+            <div className="status-msg">
+              {pullProgress.status}
+              {pullPercent !== null && ` ${pullPercent}%`}
+            </div>
+          )}
+        </>
+      )}
 
       <style>{`
         .ollama-settings {
@@ -115,6 +341,11 @@ export default function OllamaSettings({
           border-radius: 6px;
           margin-bottom: 6px;
         }
+        .status-msg {
+          font-size: 0.8em;
+          color: rgba(255, 255, 255, 0.5);
+          padding: 0 14px 6px;
+        }
         .setting-row {
           display: flex;
           align-items: center;
@@ -147,6 +378,10 @@ export default function OllamaSettings({
           border-radius: 6px;
           font-size: 0.9em;
         }
+        textarea.url-input {
+          font-family: inherit;
+          resize: vertical;
+        }
         .url-input:focus {
           outline: none;
           border-color: #646cff;
@@ -208,32 +443,6 @@ export default function OllamaSettings({
           opacity: 0.4;
           cursor: not-allowed;
         }
-        .prompt-row {
-          flex-direction: column;
-          align-items: stretch;
-          gap: 8px;
-        }
-        .prompt-row label {
-          align-self: flex-start;
-        }
-        .prompt-row textarea {
-          background: rgba(255, 255, 255, 0.08);
-          border: 1px solid rgba(255, 255, 255, 0.1);
-          color: #fff;
-          padding: 10px 12px;
-          border-radius: 6px;
-          font-family: inherit;
-          font-size: 0.85em;
-          resize: vertical;
-          min-height: 55px;
-        }
-        .prompt-row textarea:focus {
-          outline: none;
-          border-color: #646cff;
-        }
-        .prompt-row textarea::placeholder {
-          color: rgba(255, 255, 255, 0.3);
-        }
       `}</style>
     </div>
   );
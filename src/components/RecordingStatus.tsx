@@ -1,21 +1,49 @@
-type PipelineStage = 'recording' | 'transcribing' | 'refining' | 'copying' | 'done';
+type PipelineStage = 'recording' | 'paused' | 'transcribing' | 'refining' | 'copying' | 'done';
 
 interface CompactStatusProps {
   stage: PipelineStage;
+  preview?: string;
+  /** RMS level of each input channel while recording. */
+  channelLevels?: number[];
+  /** Channels mixed into the recording; the others are drawn dimmed. */
+  activeChannels?: number[];
+  /** Level of each octave band in dBFS, low to high. */
+  bandLevels?: number[];
+  clipping?: boolean;
   onStop: () => void;
+  /** Pause or resume the recording; the paused part is left out of the transcription. */
+  onPauseToggle?: () => void;
 }
 
 const stageConfig: Record<PipelineStage, { color: string; text: string; bgGlow: string }> = {
   recording: { color: '#ff4d4d', text: 'Recording', bgGlow: 'rgba(255, 77, 77, 0.15)' },
+  paused: { color: '#b0b0c0', text: 'Paused', bgGlow: 'rgba(176, 176, 192, 0.12)' },
   transcribing: { color: '#ffcc00', text: 'Transcribing', bgGlow: 'rgba(255, 204, 0, 0.15)' },
   refining: { color: '#4da6ff', text: 'Refining', bgGlow: 'rgba(77, 166, 255, 0.15)' },
   copying: { color: '#4dff88', text: 'Copying', bgGlow: 'rgba(77, 255, 136, 0.15)' },
   done: { color: '#4dff88', text: 'Copied!', bgGlow: 'rgba(77, 255, 136, 0.2)' },
 };
 
-export default function RecordingStatus({ stage, onStop }: CompactStatusProps) {
-  const pulse = stage !== 'done';
+/** Quietest band level drawn, in dBFS. */
+const METER_FLOOR_DB = -60;
+
+const bandHeight = (db: number) =>
+  Math.max(0, Math.min(100, ((db - METER_FLOOR_DB) / -METER_FLOOR_DB) * 100));
+
+export default function RecordingStatus({
+  stage,
+  preview,
+  channelLevels = [],
+  activeChannels = [],
+  bandLevels = [],
+  clipping = false,
+  onStop,
+  onPauseToggle,
+}: CompactStatusProps) {
+  const pulse = stage !== 'done' && stage !== 'paused';
   const config = stageConfig[stage];
+  const canStop = stage === 'recording' || stage === 'paused' || stage === 'refining';
+  const canPause = onPauseToggle && (stage === 'recording' || stage === 'paused');
 
   return (
     <div
@@ -34,14 +62,45 @@ export default function RecordingStatus({ stage, onStop }: CompactStatusProps) {
         <span className="status-text" style={{ color: config.color }}>
           {config.text}
         </span>
+        {preview && <span className="preview-text">{preview.slice(-60)}</span>}
+        {stage === 'recording' && bandLevels.length > 0 && (
+          <div className={`band-meters ${clipping ? 'clipping' : ''}`} title="Input spectrum">
+            {bandLevels.map((db, band) => (
+              <div key={band} className="band-meter" style={{ height: `${bandHeight(db)}%` }} />
+            ))}
+          </div>
+        )}
+        {stage === 'recording' && clipping && <span className="clip-badge">CLIP</span>}
+        {stage === 'recording' && channelLevels.length > 1 && (
+          <div className="channel-meters" title="Input level per channel">
+            {channelLevels.map((level, channel) => (
+              <div
+                key={channel}
+                className={`channel-meter ${activeChannels.includes(channel) ? 'active' : ''}`}
+              >
+                <div
+                  className="channel-meter-fill"
+                  style={{ height: `${Math.min(100, Math.sqrt(level) * 100)}%` }}
+                />
+              </div>
+            ))}
+          </div>
+        )}
+      </div>
+      <div className="status-actions">
+        {canPause && (
+          <button onClick={onPauseToggle} className="pause-button">
+            {stage === 'paused' ? 'Resume' : 'Pause'}
+          </button>
+        )}
+        <button
+          onClick={onStop}
+          className={`stop-button ${!canStop ? 'disabled' : ''}`}
+          disabled={!canStop}
+        >
+          Stop
+        </button>
       </div>
-      <button
-        onClick={onStop}
-        className={`stop-button ${stage !== 'recording' ? 'disabled' : ''}`}
-        disabled={stage !== 'recording'}
-      >
-        Stop
-      </button>
       <style>{`
         .compact-status {
           display: flex;
@@ -82,6 +141,78 @@ export default function RecordingStatus({ stage, onStop }: CompactStatusProps) {
           letter-spacing: 0.02em;
           min-width: 120px;
         }
+        .preview-text {
+          font-size: 0.8em;
+          color: rgba(255, 255, 255, 0.6);
+          max-width: 160px;
+          overflow: hidden;
+          white-space: nowrap;
+          text-overflow: ellipsis;
+        }
+        .band-meters {
+          display: flex;
+          align-items: flex-end;
+          gap: 2px;
+          height: 24px;
+        }
+        .band-meter {
+          width: 4px;
+          min-height: 1px;
+          background: rgba(255, 255, 255, 0.6);
+          border-radius: 1px;
+          transition: height 0.05s linear;
+        }
+        .band-meters.clipping .band-meter {
+          background: #ff4d4d;
+        }
+        .clip-badge {
+          font-size: 0.7em;
+          font-weight: 700;
+          color: #ff4d4d;
+          letter-spacing: 0.05em;
+        }
+        .channel-meters {
+          display: flex;
+          align-items: flex-end;
+          gap: 3px;
+          height: 24px;
+        }
+        .channel-meter {
+          width: 5px;
+          height: 100%;
+          display: flex;
+          align-items: flex-end;
+          background: rgba(255, 255, 255, 0.08);
+          border-radius: 2px;
+          opacity: 0.4;
+        }
+        .channel-meter.active {
+          opacity: 1;
+        }
+        .channel-meter-fill {
+          width: 100%;
+          background: #4dff88;
+          border-radius: 2px;
+          transition: height 0.05s linear;
+        }
+        .status-actions {
+          display: flex;
+          gap: 8px;
+        }
+        .pause-button {
+          background: #2a2a35;
+          color: #f0f0f5;
+          border: 1px solid #3a3a45;
+          padding: 8px 16px;
+          border-radius: 6px;
+          cursor: pointer;
+          font-weight: 600;
+          font-size: 0.9em;
+          transition: all 0.2s ease;
+        }
+        .pause-button:hover {
+          background: #3a3a45;
+        }
         .stop-button {
           background: linear-gradient(135deg, #ff4d4d 0%, #cc0000 100%);
           color: white;
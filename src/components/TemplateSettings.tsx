@@ -0,0 +1,453 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { errorCode, errorMessage } from '../errors';
+
+export interface FewShotExample {
+  input: string;
+  output: string;
+}
+
+export interface GenerationOptions {
+  temperature: number | null;
+  top_p: number | null;
+  num_ctx: number | null;
+  seed: number | null;
+  stop: string[];
+  keep_alive: string | null;
+  json: boolean;
+  timeout_secs: number | null;
+}
+
+export interface PromptTemplate {
+  id: string;
+  name: string;
+  prompt: string;
+  system_prompt: string;
+  examples: FewShotExample[];
+  shortcut: string | null;
+  options: GenerationOptions;
+  check_divergence: boolean;
+}
+
+interface TemplateList {
+  active: string | null;
+  templates: PromptTemplate[];
+  load_error: string | null;
+}
+
+const defaultOptions = (): GenerationOptions => ({
+  temperature: null,
+  top_p: null,
+  num_ctx: null,
+  seed: null,
+  stop: [],
+  keep_alive: null,
+  json: false,
+  timeout_secs: null,
+});
+
+const newTemplate = (): PromptTemplate => ({
+  id: '',
+  name: 'New Template',
+  prompt: '{text}',
+  system_prompt: '',
+  examples: [],
+  shortcut: null,
+  options: defaultOptions(),
+  check_divergence: true,
+});
+
+const parseNumber = (value: string) => (value.trim() === '' ? null : Number(value));
+
+const numericOptions: { key: 'temperature' | 'top_p' | 'num_ctx' | 'seed'; label: string }[] = [
+  { key: 'temperature', label: 'Temperature' },
+  { key: 'top_p', label: 'Top P' },
+  { key: 'num_ctx', label: 'Context Size' },
+  { key: 'seed', label: 'Seed' },
+];
+
+export default function TemplateSettings() {
+  const [templates, setTemplates] = useState<PromptTemplate[]>([]);
+  const [active, setActive] = useState<string | null>(null);
+  const [draft, setDraft] = useState<PromptTemplate | null>(null);
+  const [chatMode, setChatMode] = useState(false);
+  const [error, setError] = useState('');
+  const [loadError, setLoadError] = useState<string | null>(null);
+
+  const applyList = (list: TemplateList) => {
+    setTemplates(list.templates);
+    setActive(list.active);
+    setLoadError(list.load_error);
+    const current = list.templates.find((t) => t.id === list.active) || list.templates[0] || null;
+    setDraft(current);
+    setChatMode(!!current?.system_prompt);
+  };
+
+  useEffect(() => {
+    invoke<TemplateList>('list_templates').then(applyList).catch(console.error);
+    const unlisten = listen('templates-changed', () => {
+      invoke<TemplateList>('list_templates').then(applyList).catch(console.error);
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const run = async (command: string, args?: Record<string, unknown>) => {
+    setError('');
+    try {
+      applyList(await invoke<TemplateList>(command, args));
+    } catch (err) {
+      if (errorCode(err) === 'no_file_selected') return;
+      console.error(`${command} failed:`, err);
+      setError(errorMessage(err));
+    }
+  };
+
+  const update = (patch: Partial<PromptTemplate>) => {
+    if (draft) setDraft({ ...draft, ...patch });
+  };
+
+  const options = draft?.options ?? defaultOptions();
+  const updateOptions = (patch: Partial<GenerationOptions>) => {
+    update({ options: { ...options, ...patch } });
+  };
+
+  const updateExample = (index: number, field: keyof FewShotExample, value: string) => {
+    if (!draft) return;
+    update({
+      examples: draft.examples.map((ex, i) => (i === index ? { ...ex, [field]: value } : ex)),
+    });
+  };
+
+  const save = () => {
+    if (!draft) return;
+    run('save_template', {
+      template: {
+        ...draft,
+        system_prompt: chatMode ? draft.system_prompt : '',
+        shortcut: draft.shortcut || null,
+        options,
+      },
+    });
+  };
+
+  const exportTemplates = async () => {
+    try {
+      await invoke<string>('export_templates');
+    } catch (err) {
+      if (errorCode(err) === 'no_file_selected') return;
+      console.error('Failed to export templates:', err);
+      setError(errorMessage(err));
+    }
+  };
+
+  return (
+    <div className="template-settings">
+      {loadError && <div className="error-msg">{loadError}</div>}
+      {error && <div className="error-msg">{error}</div>}
+
+      <div className="setting-row">
+        <label>Template</label>
+        <div className="input-group">
+          <div className="select-wrapper">
+            <select
+              value={active || ''}
+              onChange={(e) => run('set_active_template', { id: e.target.value || null })}
+            >
+              {templates.map((t) => (
+                <option key={t.id} value={t.id}>
+                  {t.name}
+                </option>
+              ))}
+            </select>
+            <span className="select-arrow">▼</span>
+          </div>
+          <button
+            onClick={() => {
+              setDraft(newTemplate());
+              setChatMode(false);
+            }}
+            className="icon-btn"
+            title="New template"
+          >
+            +
+          </button>
+        </div>
+      </div>
+
+      {draft && (
+        <>
+          <div className="setting-row">
+            <label>Name</label>
+            <div className="input-group">
+              <input
+                type="text"
+                value={draft.name}
+                onChange={(e) => update({ name: e.target.value })}
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>Shortcut</label>
+            <div className="input-group">
+              <input
+                type="text"
+                value={draft.shortcut || ''}
+                onChange={(e) => update({ shortcut: e.target.value })}
+                placeholder="e.g. Ctrl+Alt+1"
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>Prompt Style</label>
+            <div className="input-group">
+              <div className="select-wrapper">
+                <select
+                  value={chatMode ? 'chat' : 'single'}
+                  onChange={(e) => setChatMode(e.target.value === 'chat')}
+                >
+                  <option value="single">Single prompt</option>
+                  <option value="chat">Chat (system + examples)</option>
+                </select>
+                <span className="select-arrow">▼</span>
+              </div>
+            </div>
+          </div>
+
+          {chatMode ? (
+            <>
+              <div className="setting-row prompt-row">
+                <label>System Prompt</label>
+                <textarea
+                  value={draft.system_prompt}
+                  onChange={(e) => update({ system_prompt: e.target.value })}
+                  placeholder="Instructions for cleaning the transcript..."
+                  rows={3}
+                />
+              </div>
+              <div className="setting-row prompt-row">
+                <label>Examples</label>
+                {draft.examples.map((example, index) => (
+                  <div key={index} className="example-pair">
+                    <textarea
+                      value={example.input}
+                      onChange={(e) => updateExample(index, 'input', e.target.value)}
+                      placeholder="Raw transcript"
+                      rows={2}
+                    />
+                    <span className="example-arrow">→</span>
+                    <textarea
+                      value={example.output}
+                      onChange={(e) => updateExample(index, 'output', e.target.value)}
+                      placeholder="Cleaned text"
+                      rows={2}
+                    />
+                    <button
+                      onClick={() =>
+                        update({ examples: draft.examples.filter((_, i) => i !== index) })
+                      }
+                      className="icon-btn"
+                      title="Remove example"
+                    >
+                      ×
+                    </button>
+                  </div>
+                ))}
+                <button
+                  onClick={() =>
+                    update({ examples: [...draft.examples, { input: '', output: '' }] })
+                  }
+                  className="icon-btn"
+                  title="Add example"
+                >
+                  +
+                </button>
+              </div>
+            </>
+          ) : (
+            <div className="setting-row prompt-row">
+              <label>Prompt Template</label>
+              <textarea
+                value={draft.prompt}
+                onChange={(e) => update({ prompt: e.target.value })}
+                placeholder="Use {text} as placeholder..."
+                rows={3}
+              />
+            </div>
+          )}
+
+          {numericOptions.map(({ key, label }) => (
+            <div className="setting-row" key={key}>
+              <label>{label}</label>
+              <div className="input-group">
+                <input
+                  type="number"
+                  step="any"
+                  value={options[key] ?? ''}
+                  onChange={(e) => updateOptions({ [key]: parseNumber(e.target.value) })}
+                  placeholder="Server default"
+                  className="url-input"
+                />
+              </div>
+            </div>
+          ))}
+
+          <div className="setting-row">
+            <label>Stop Sequences</label>
+            <div className="input-group">
+              <input
+                type="text"
+                value={options.stop.join(', ')}
+                onChange={(e) =>
+                  updateOptions({
+                    stop: e.target.value
+                      .split(',')
+                      .map((s) => s.trim())
+                      .filter((s) => s),
+                  })
+                }
+                placeholder="Comma separated"
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>Keep Alive</label>
+            <div className="input-group">
+              <input
+                type="text"
+                value={options.keep_alive ?? ''}
+                onChange={(e) => updateOptions({ keep_alive: e.target.value || null })}
+                placeholder="e.g. 30m, -1 to keep loaded"
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>Timeout (seconds)</label>
+            <div className="input-group">
+              <input
+                type="number"
+                min="1"
+                value={options.timeout_secs ?? ''}
+                onChange={(e) => updateOptions({ timeout_secs: parseNumber(e.target.value) })}
+                placeholder="60"
+                className="url-input"
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>JSON Output</label>
+            <div className="input-group">
+              <input
+                type="checkbox"
+                checked={options.json}
+                onChange={(e) => updateOptions({ json: e.target.checked })}
+              />
+            </div>
+          </div>
+
+          <div className="setting-row">
+            <label>Fall Back If Output Diverges</label>
+            <div className="input-group">
+              <input
+                type="checkbox"
+                checked={draft.check_divergence}
+                onChange={(e) => update({ check_divergence: e.target.checked })}
+                title="Turn off for templates that translate or summarize"
+              />
+            </div>
+          </div>
+
+          <p className="variables-hint">
+            Variables: {'{text}'} {'{language}'} {'{date}'} {'{app_name}'} {'{clipboard}'}{' '}
+            {'{previous_transcript}'}
+          </p>
+        </>
+      )}
+
+      <div className="template-actions">
+        <button onClick={save} disabled={!draft} className="select-btn">
+          Save
+        </button>
+        <button
+          onClick={() => draft?.id && run('delete_template', { id: draft.id })}
+          disabled={!draft?.id}
+          className="select-btn"
+        >
+          Delete
+        </button>
+        <button onClick={() => run('import_templates')} className="select-btn">
+          Import
+        </button>
+        <button onClick={exportTemplates} className="select-btn">
+          Export
+        </button>
+      </div>
+
+      <style>{`
+        .prompt-row {
+          flex-direction: column;
+          align-items: stretch;
+          gap: 8px;
+        }
+        .prompt-row label {
+          align-self: flex-start;
+        }
+        .prompt-row textarea {
+          background: rgba(255, 255, 255, 0.08);
+          border: 1px solid rgba(255, 255, 255, 0.1);
+          color: #fff;
+          padding: 10px 12px;
+          border-radius: 6px;
+          font-family: inherit;
+          font-size: 0.85em;
+          resize: vertical;
+          min-height: 55px;
+        }
+        .prompt-row textarea:focus {
+          outline: none;
+          border-color: #646cff;
+        }
+        .example-pair {
+          display: flex;
+          align-items: center;
+          gap: 6px;
+        }
+        .example-pair textarea {
+          flex: 1;
+          min-height: 40px;
+        }
+        .example-arrow {
+          color: rgba(255, 255, 255, 0.4);
+        }
+        .prompt-row textarea::placeholder {
+          color: rgba(255, 255, 255, 0.3);
+        }
+        .variables-hint {
+          font-size: 0.75em;
+          color: rgba(255, 255, 255, 0.4);
+          margin: 4px 14px 8px;
+        }
+        .template-actions {
+          display: flex;
+          gap: 8px;
+          justify-content: flex-end;
+        }
+        .template-actions .select-btn:disabled {
+          opacity: 0.4;
+          cursor: not-allowed;
+        }
+      `}</style>
+    </div>
+  );
+}
@@ -0,0 +1,17 @@
+/** Error returned by backend commands; see `AppError` in `src-tauri/src/error.rs`. */
+export interface AppError {
+  code: string;
+  message: string;
+  context: string | null;
+}
+
+export const isAppError = (err: unknown): err is AppError =>
+  typeof err === 'object' && err !== null && 'code' in err && 'message' in err;
+
+export const errorCode = (err: unknown): string | null => (isAppError(err) ? err.code : null);
+
+export const errorMessage = (err: unknown): string => {
+  if (isAppError(err)) return err.message;
+  if (err instanceof Error) return err.message;
+  return String(err);
+};